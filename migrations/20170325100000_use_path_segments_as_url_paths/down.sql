@@ -0,0 +1 @@
+-- the full urls can't be recovered, and the path segments still work
@@ -0,0 +1,6 @@
+-- url_path used to hold the project's whole GitHub url, like
+-- https://github.com/rust-lang/rust/, but it's now the path the project is
+-- served under, like rust
+UPDATE projects
+SET url_path = lower(regexp_replace(regexp_replace(url_path, '/+$', ''), '^.*/', ''))
+WHERE url_path LIKE '%/%';
@@ -0,0 +1,3 @@
+ALTER TABLE releases ADD COLUMN published_at TIMESTAMP;
+
+CREATE INDEX releases_published_at_idx ON releases(published_at) WHERE published_at IS NOT NULL;
@@ -0,0 +1,3 @@
+ALTER TABLE commits
+	DROP COLUMN authored_at,
+	DROP COLUMN committed_at;
@@ -0,0 +1,5 @@
+ALTER TABLE commits
+	ADD COLUMN authored_at TIMESTAMP,
+	ADD COLUMN committed_at TIMESTAMP;
+
+CREATE INDEX commits_authored_at_idx ON commits USING btree (authored_at);
@@ -0,0 +1,2 @@
+DROP VIEW contributions;
+DROP TABLE co_authors;
@@ -0,0 +1,14 @@
+CREATE TABLE co_authors (
+	sha VARCHAR NOT NULL REFERENCES commits (sha) ON DELETE CASCADE,
+	author_id INTEGER NOT NULL REFERENCES authors (id) ON DELETE CASCADE,
+	PRIMARY KEY (sha, author_id)
+);
+
+CREATE INDEX co_authors_author_id_idx ON co_authors USING btree (author_id);
+
+-- every commit credited to each of its authors, co-authors included
+CREATE VIEW contributions AS
+	SELECT sha, release_id, author_id, authored_at FROM commits
+	UNION
+	SELECT commits.sha, commits.release_id, co_authors.author_id, commits.authored_at
+	FROM co_authors INNER JOIN commits ON commits.sha = co_authors.sha;
@@ -0,0 +1,9 @@
+DROP VIEW contributions;
+
+CREATE VIEW contributions AS
+	SELECT sha, release_id, author_id, authored_at FROM commits
+	UNION
+	SELECT commits.sha, commits.release_id, co_authors.author_id, commits.authored_at
+	FROM co_authors INNER JOIN commits ON commits.sha = co_authors.sha;
+
+DROP TABLE author_aliases;
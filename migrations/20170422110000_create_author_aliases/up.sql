@@ -0,0 +1,25 @@
+-- authors that are really the same person as another, canonical, author
+CREATE TABLE author_aliases (
+	alias_id INTEGER PRIMARY KEY REFERENCES authors (id) ON DELETE CASCADE,
+	canonical_id INTEGER NOT NULL REFERENCES authors (id) ON DELETE CASCADE,
+	CHECK (alias_id <> canonical_id)
+);
+
+CREATE INDEX author_aliases_canonical_id_idx ON author_aliases USING btree (canonical_id);
+
+-- credit aliases' commits to their canonical author
+DROP VIEW contributions;
+
+CREATE VIEW contributions AS
+	SELECT commits.sha, commits.release_id,
+		COALESCE(author_aliases.canonical_id, commits.author_id) AS author_id,
+		commits.authored_at
+	FROM commits
+	LEFT OUTER JOIN author_aliases ON author_aliases.alias_id = commits.author_id
+	UNION
+	SELECT commits.sha, commits.release_id,
+		COALESCE(author_aliases.canonical_id, co_authors.author_id) AS author_id,
+		commits.authored_at
+	FROM co_authors
+	INNER JOIN commits ON commits.sha = co_authors.sha
+	LEFT OUTER JOIN author_aliases ON author_aliases.alias_id = co_authors.author_id;
@@ -0,0 +1,5 @@
+-- whether the author asked to be hidden themselves, as opposed to being
+-- hidden because someone merged into them did
+ALTER TABLE authors ADD COLUMN opted_out BOOLEAN NOT NULL DEFAULT FALSE;
+
+UPDATE authors SET opted_out = TRUE WHERE visible = FALSE;
@@ -0,0 +1,2 @@
+-- where to find the project's .mailmap, if not in the usual place
+ALTER TABLE projects ADD COLUMN mailmap VARCHAR;
@@ -0,0 +1,3 @@
+-- how the project names its release tags, with {version} standing for the
+-- version, like v{version}
+ALTER TABLE projects ADD COLUMN tag_pattern VARCHAR NOT NULL DEFAULT '{version}';
@@ -0,0 +1,2 @@
+-- the release this one follows, when that isn't every earlier release
+ALTER TABLE releases ADD COLUMN previous_version VARCHAR;
@@ -0,0 +1,20 @@
+-- the commit this one was cherry-picked from, when we have both
+ALTER TABLE commits ADD COLUMN backport_of VARCHAR;
+
+CREATE INDEX commits_backport_of_idx ON commits(backport_of) WHERE backport_of IS NOT NULL;
+
+DROP VIEW contributions;
+
+CREATE VIEW contributions AS
+	SELECT commits.sha, commits.release_id,
+		COALESCE(author_aliases.canonical_id, commits.author_id) AS author_id,
+		commits.authored_at, commits.backport_of
+	FROM commits
+	LEFT OUTER JOIN author_aliases ON author_aliases.alias_id = commits.author_id
+	UNION
+	SELECT commits.sha, commits.release_id,
+		COALESCE(author_aliases.canonical_id, co_authors.author_id) AS author_id,
+		commits.authored_at, commits.backport_of
+	FROM co_authors
+	INNER JOIN commits ON commits.sha = co_authors.sha
+	LEFT OUTER JOIN author_aliases ON author_aliases.alias_id = co_authors.author_id;
@@ -0,0 +1,9 @@
+-- how far we've synced each repository's commits from GitHub
+CREATE TABLE github_syncs (
+	repository VARCHAR PRIMARY KEY,
+	since TIMESTAMP NOT NULL,
+	etag VARCHAR,
+	-- when the last successful sync started, so that the next one picks up
+	-- from there however long ago it was
+	synced_at TIMESTAMP NOT NULL
+);
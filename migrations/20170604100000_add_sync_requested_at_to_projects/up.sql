@@ -0,0 +1,3 @@
+-- set when a push was too big to record from its webhook delivery, until
+-- update-commit-db fetches the project's commits from the API
+ALTER TABLE projects ADD COLUMN sync_requested_at TIMESTAMP;
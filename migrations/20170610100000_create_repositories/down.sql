@@ -0,0 +1,18 @@
+ALTER TABLE projects
+	ADD COLUMN mailmap VARCHAR,
+	ADD COLUMN tag_pattern VARCHAR NOT NULL DEFAULT '{version}',
+	ADD COLUMN webhook_secret VARCHAR,
+	ADD COLUMN sync_requested_at TIMESTAMP;
+
+-- only the repository projects are named after survives
+UPDATE projects SET
+	mailmap = repositories.mailmap,
+	tag_pattern = repositories.tag_pattern,
+	webhook_secret = repositories.webhook_secret,
+	sync_requested_at = repositories.sync_requested_at
+FROM repositories
+WHERE repositories.project_id = projects.id
+	AND repositories.github_name = projects.github_name;
+
+DROP TABLE repository_tags;
+DROP TABLE repositories;
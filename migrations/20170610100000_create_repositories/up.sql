@@ -0,0 +1,31 @@
+-- the repositories whose commits make up a project's releases
+CREATE TABLE repositories (
+	id SERIAL PRIMARY KEY,
+	project_id INTEGER NOT NULL REFERENCES projects (id) ON DELETE CASCADE,
+	github_name VARCHAR NOT NULL,
+	tag_pattern VARCHAR NOT NULL DEFAULT '{version}',
+	mailmap VARCHAR,
+	webhook_secret VARCHAR,
+	sync_requested_at TIMESTAMP
+);
+
+CREATE UNIQUE INDEX repositories_github_name_idx ON repositories USING btree (github_name);
+CREATE INDEX repositories_project_id_idx ON repositories USING btree (project_id);
+
+-- tags that don't follow a repository's tag pattern
+CREATE TABLE repository_tags (
+	repository_id INTEGER NOT NULL REFERENCES repositories (id) ON DELETE CASCADE,
+	release_id INTEGER NOT NULL REFERENCES releases (id) ON DELETE CASCADE,
+	tag VARCHAR NOT NULL,
+	PRIMARY KEY (repository_id, release_id)
+);
+
+-- every project starts out with the repository it was made from
+INSERT INTO repositories (project_id, github_name, tag_pattern, mailmap, webhook_secret, sync_requested_at)
+	SELECT id, github_name, tag_pattern, mailmap, webhook_secret, sync_requested_at FROM projects;
+
+ALTER TABLE projects
+	DROP COLUMN tag_pattern,
+	DROP COLUMN mailmap,
+	DROP COLUMN webhook_secret,
+	DROP COLUMN sync_requested_at;
@@ -0,0 +1,19 @@
+DROP VIEW contributions;
+
+CREATE VIEW contributions AS
+	SELECT commits.sha, commits.release_id,
+		COALESCE(author_aliases.canonical_id, commits.author_id) AS author_id,
+		commits.authored_at, commits.backport_of
+	FROM commits
+	LEFT OUTER JOIN author_aliases ON author_aliases.alias_id = commits.author_id
+	UNION
+	SELECT commits.sha, commits.release_id,
+		COALESCE(author_aliases.canonical_id, co_authors.author_id) AS author_id,
+		commits.authored_at, commits.backport_of
+	FROM co_authors
+	INNER JOIN commits ON commits.sha = co_authors.sha
+	LEFT OUTER JOIN author_aliases ON author_aliases.alias_id = co_authors.author_id;
+
+DROP TABLE bots;
+
+ALTER TABLE repositories DROP COLUMN default_branch;
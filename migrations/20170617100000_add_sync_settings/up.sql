@@ -0,0 +1,38 @@
+-- the branch unreleased commits are on, which the `master` release follows
+ALTER TABLE repositories ADD COLUMN default_branch VARCHAR NOT NULL DEFAULT 'master';
+
+-- authors who are bots, by email, and shouldn't be thanked in a project
+CREATE TABLE bots (
+	project_id INTEGER NOT NULL REFERENCES projects (id) ON DELETE CASCADE,
+	email VARCHAR NOT NULL,
+	PRIMARY KEY (project_id, email)
+);
+
+-- a bot's commits still count for its co-authors
+DROP VIEW contributions;
+
+CREATE VIEW contributions AS
+	SELECT commits.sha, commits.release_id,
+		COALESCE(author_aliases.canonical_id, commits.author_id) AS author_id,
+		commits.authored_at, commits.backport_of
+	FROM commits
+	LEFT OUTER JOIN author_aliases ON author_aliases.alias_id = commits.author_id
+	WHERE NOT EXISTS (
+		SELECT 1 FROM bots
+		INNER JOIN releases ON releases.project_id = bots.project_id
+		INNER JOIN authors ON lower(authors.email) = lower(bots.email)
+		WHERE releases.id = commits.release_id AND authors.id = commits.author_id
+	)
+	UNION
+	SELECT commits.sha, commits.release_id,
+		COALESCE(author_aliases.canonical_id, co_authors.author_id) AS author_id,
+		commits.authored_at, commits.backport_of
+	FROM co_authors
+	INNER JOIN commits ON commits.sha = co_authors.sha
+	LEFT OUTER JOIN author_aliases ON author_aliases.alias_id = co_authors.author_id
+	WHERE NOT EXISTS (
+		SELECT 1 FROM bots
+		INNER JOIN releases ON releases.project_id = bots.project_id
+		INNER JOIN authors ON lower(authors.email) = lower(bots.email)
+		WHERE releases.id = commits.release_id AND authors.id = co_authors.author_id
+	);
@@ -0,0 +1 @@
+DROP TABLE assigned_releases;
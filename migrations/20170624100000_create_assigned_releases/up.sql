@@ -0,0 +1,7 @@
+-- the releases whose commits were assigned from a repository, so that
+-- populate can pick up where it left off
+CREATE TABLE assigned_releases (
+	repository_id INTEGER NOT NULL REFERENCES repositories (id) ON DELETE CASCADE,
+	release_id INTEGER NOT NULL REFERENCES releases (id) ON DELETE CASCADE,
+	PRIMARY KEY (repository_id, release_id)
+);
@@ -0,0 +1,104 @@
+//! The types served by the `/api/v1` endpoints.
+//!
+//! These are kept separate from the models so that the shape of the API
+//! doesn't change every time the database schema does.
+
+use dates::DateRange;
+use models::Project;
+use releases::RangeContributor;
+
+use Score;
+
+#[derive(Debug, Serialize)]
+pub struct ProjectInfo {
+    pub name: String,
+    pub url_path: String,
+    pub github_name: String,
+}
+
+impl<'a> From<&'a Project> for ProjectInfo {
+    fn from(project: &'a Project) -> ProjectInfo {
+        ProjectInfo {
+            name: project.name.clone(),
+            url_path: project.url_path.clone(),
+            github_name: project.github_name.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Projects {
+    pub projects: Vec<ProjectInfo>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Releases {
+    pub project: String,
+    pub releases: Vec<String>,
+}
+
+/// the contributors to a release
+///
+/// `since` and `until` are `None` when the list isn't limited to that end
+#[derive(Debug, Serialize)]
+pub struct Contributors {
+    pub project: String,
+    pub release: String,
+    pub since: Option<String>,
+    pub until: Option<String>,
+    pub count: usize,
+    pub contributors: Vec<String>,
+    /// the contributors whose earliest commit is in this release
+    pub first_time_contributors: Vec<String>,
+}
+
+/// the contributors to every release from `from` to `to`, inclusive
+///
+/// `since` and `until` are as in `Contributors`
+#[derive(Debug, Serialize)]
+pub struct RangeContributors {
+    pub project: String,
+    pub from: String,
+    pub to: String,
+    pub since: Option<String>,
+    pub until: Option<String>,
+    pub count: usize,
+    pub contributors: Vec<RangeContributor>,
+}
+
+/// a leaderboard
+///
+/// `project` is `None` for the organization-wide leaderboard, and `since`
+/// and `until` are `None` when the leaderboard isn't limited to that end
+#[derive(Debug, Serialize)]
+pub struct Scores {
+    pub project: Option<String>,
+    pub since: Option<String>,
+    pub until: Option<String>,
+    pub count: usize,
+    pub scores: Vec<Score>,
+}
+
+impl Scores {
+    pub fn new(project: Option<String>, range: &DateRange, scores: Vec<Score>) -> Scores {
+        Scores {
+            project: project,
+            since: range.since.map(|date| date.to_string()),
+            until: range.until.map(|date| date.to_string()),
+            count: scores.len(),
+            scores: scores,
+        }
+    }
+}
+
+/// the body of every non-200 response
+#[derive(Debug, Serialize)]
+pub struct ApiError {
+    pub error: String,
+}
+
+impl ApiError {
+    pub fn new<S: Into<String>>(message: S) -> ApiError {
+        ApiError { error: message.into() }
+    }
+}
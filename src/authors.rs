@@ -1,16 +1,18 @@
-use models::{Author, NewAuthor};
+use dates::DateRange;
+use models::{Author, AuthorAlias, NewAuthor, Project, Release};
 
 use diesel::*;
 use diesel::pg::PgConnection;
 
-pub fn load_or_create(conn: &PgConnection, author_name: &str, author_email: &str) -> Author {
+use std::error::Error;
+
+pub fn load_or_create(conn: &PgConnection, author_name: &str, author_email: &str) -> QueryResult<Author> {
     let new_author = NewAuthor {
         name: author_name,
         email: author_email,
     };
 
     find_or_create(conn, new_author)
-        .expect("Could not find or create author")
 }
 
 pub fn find_or_create_all(conn: &PgConnection, new_authors: Vec<NewAuthor>)
@@ -50,3 +52,225 @@ fn find_or_create(conn: &PgConnection, new_author: NewAuthor) -> QueryResult<Aut
         .filter(email.eq(new_author.email))
         .first(conn)
 }
+
+/// the id of the author that `author` is an alias of, or `author` itself
+pub fn canonical_id(conn: &PgConnection, author: i32) -> i32 {
+    use schema::author_aliases::dsl::*;
+
+    author_aliases.find(author)
+        .select(canonical_id)
+        .first(conn)
+        .optional()
+        .expect("Error loading author alias")
+        .unwrap_or(author)
+}
+
+/// every author that has been merged into `canonical`
+pub fn aliases(conn: &PgConnection, canonical: i32) -> Vec<Author> {
+    use schema::authors;
+    use schema::author_aliases;
+    use diesel::expression::dsl::any;
+
+    let alias_ids = author_aliases::table
+        .filter(author_aliases::canonical_id.eq(canonical))
+        .select(author_aliases::alias_id);
+
+    authors::table
+        .filter(authors::id.eq(any(alias_ids)))
+        .order(authors::id)
+        .load(conn)
+        .expect("Error loading aliases")
+}
+
+/// merges `alias` into `canonical`, so that they're counted as one person
+///
+/// if `canonical` is itself an alias, `alias` is merged into the author it
+/// points at instead, and anyone already merged into `alias` moves along with
+/// it. returns the id `alias` was merged into.
+pub fn merge(conn: &PgConnection, canonical: i32, alias: i32) -> Result<i32, Box<Error>> {
+    use schema::authors;
+    use schema::author_aliases;
+
+    for &author in &[canonical, alias] {
+        if authors::table.find(author).first::<Author>(conn).optional()?.is_none() {
+            return Err(format!("There is no author with id {}", author).into());
+        }
+    }
+
+    let canonical = canonical_id(conn, canonical);
+    if canonical == alias {
+        return Err(format!("Author {} can't be merged into itself", alias).into());
+    }
+
+    conn.transaction::<_, Box<Error>, _>(|| {
+        let previous_canonical = canonical_id(conn, alias);
+
+        delete(author_aliases::table.find(alias))
+            .execute(conn)?;
+
+        update(author_aliases::table.filter(author_aliases::canonical_id.eq(alias)))
+            .set(author_aliases::canonical_id.eq(canonical))
+            .execute(conn)?;
+
+        let new_alias = AuthorAlias {
+            alias_id: alias,
+            canonical_id: canonical,
+        };
+        insert(&new_alias).into(author_aliases::table)
+            .execute(conn)?;
+
+        // merging someone who opted out hides the whole person
+        update_visibility(conn, canonical)?;
+        if previous_canonical != alias {
+            update_visibility(conn, previous_canonical)?;
+        }
+
+        Ok(canonical)
+    })
+}
+
+/// separates `alias` from whoever it was merged into
+///
+/// both of them are shown again, unless they or anyone still merged into
+/// them opted out themselves. returns `false` if it wasn't an alias.
+pub fn unmerge(conn: &PgConnection, alias: i32) -> QueryResult<bool> {
+    use schema::author_aliases;
+
+    conn.transaction(|| {
+        let canonical = canonical_id(conn, alias);
+
+        let deleted = delete(author_aliases::table.find(alias))
+            .execute(conn)?;
+
+        update_visibility(conn, canonical)?;
+        update_visibility(conn, alias)?;
+
+        Ok(deleted > 0)
+    })
+}
+
+/// hides `canonical` if they or anyone merged into them opted out, and shows
+/// them otherwise
+pub fn update_visibility(conn: &PgConnection, canonical: i32) -> QueryResult<()> {
+    use schema::authors;
+    use schema::author_aliases;
+    use diesel::expression::dsl::any;
+
+    let alias_ids = author_aliases::table
+        .filter(author_aliases::canonical_id.eq(canonical))
+        .select(author_aliases::alias_id);
+
+    let opted_out: i64 = authors::table
+        .filter(authors::id.eq(canonical).or(authors::id.eq(any(alias_ids))))
+        .filter(authors::opted_out.eq(true))
+        .count()
+        .get_result(conn)?;
+
+    update(authors::table.find(canonical))
+        .set(authors::visible.eq(opted_out == 0))
+        .execute(conn)?;
+
+    Ok(())
+}
+
+/// how many commits an author made to a single release
+#[derive(Debug, Serialize)]
+pub struct ReleaseContribution {
+    pub version: String,
+    pub commits: i64,
+}
+
+/// everything an author has contributed to one project
+#[derive(Debug, Serialize)]
+pub struct Profile {
+    pub id: i32,
+    pub name: String,
+    pub project: String,
+    /// the author's all-time rank within the project
+    pub rank: Option<u64>,
+    /// counted like on the leaderboard, so commits to hidden releases are
+    /// included even though those releases aren't listed
+    pub commits: i64,
+    pub first_release: Option<String>,
+    pub latest_release: Option<String>,
+    /// sorted from oldest to newest
+    pub releases: Vec<ReleaseContribution>,
+}
+
+/// builds the profile of an author for a project
+///
+/// aliases get the profile of the author they were merged into. returns
+/// `None` if the author doesn't exist, has opted out, or has no commits in
+/// the project.
+pub fn profile(conn: &PgConnection, project: &Project, author: i32) -> Option<Profile> {
+    use schema::contributions;
+    use schema::releases;
+    use diesel::expression::dsl::{any, sql};
+    use diesel::types::BigInt;
+
+    let author = match authors_by_id(conn, canonical_id(conn, author)) {
+        Some(author) => author,
+        None => return None,
+    };
+
+    // every release, like the leaderboard the rank comes from
+    let mut project_releases = releases::table
+        .filter(releases::project_id.eq(project.id))
+        .load::<Release>(conn)
+        .expect("Error loading releases");
+    ::releases::sort(&mut project_releases);
+
+    let release_ids: Vec<i32> = project_releases.iter().map(|r| r.id).collect();
+
+    let counts: Vec<(i32, i64)> = contributions::table
+        .filter(contributions::author_id.eq(author.id))
+        .filter(contributions::release_id.eq(any(release_ids)))
+        // backports were already counted when they landed on master, like
+        // they are for the rank
+        .filter(contributions::backport_of.is_null())
+        .select((contributions::release_id, sql::<BigInt>("COUNT(*)")))
+        .group_by(contributions::release_id)
+        .load(conn)
+        .expect("Error counting commits");
+
+    if counts.is_empty() {
+        return None;
+    }
+
+    let releases: Vec<ReleaseContribution> = project_releases.into_iter()
+        .filter(|release| release.visible)
+        .filter_map(|release| {
+            counts.iter()
+                .find(|&&(release_id, _)| release_id == release.id)
+                .map(|&(_, count)| ReleaseContribution {
+                    version: release.version,
+                    commits: count,
+                })
+        })
+        .collect();
+
+    let rank = ::ranked_scores(conn, project, &DateRange::all()).into_iter()
+        .find(|score| score.author_id == author.id)
+        .map(|score| score.rank);
+
+    Some(Profile {
+        id: author.id,
+        name: author.name,
+        project: project.name.clone(),
+        rank: rank,
+        commits: counts.iter().map(|&(_, count)| count).sum(),
+        first_release: releases.first().map(|r| r.version.clone()),
+        latest_release: releases.last().map(|r| r.version.clone()),
+        releases: releases,
+    })
+}
+
+fn authors_by_id(conn: &PgConnection, author: i32) -> Option<Author> {
+    use schema::authors::dsl::*;
+
+    authors.find(author)
+        .filter(visible.eq(true))
+        .first(conn)
+        .optional()
+        .expect("Error loading author")
+}
@@ -0,0 +1,141 @@
+//! Commands that change how authors are credited.
+
+use {dry_run, parse_id, CommandResult, Context, Error};
+
+use clap::{App, Arg, ArgMatches, SubCommand};
+
+use diesel;
+use diesel::prelude::*;
+
+use thanks::models::Author;
+
+pub fn commands() -> Vec<App<'static, 'static>> {
+    vec![
+        SubCommand::with_name("opt-out")
+            .about("mark an author as opted-out")
+            .arg(Arg::with_name("email")
+                .short("e")
+                .long("email")
+                .help("email of the author")
+                .takes_value(true)
+                .required(true))
+            .arg(Arg::with_name("opt-in")
+                .long("opt-in")
+                .help("Use this to mark author as opted-in again"))
+            .arg(dry_run::arg()),
+        SubCommand::with_name("merge-authors")
+            .about("count several authors as one person")
+            .arg(Arg::with_name("into")
+                .short("i")
+                .long("into")
+                .help("id of the author to merge into")
+                .takes_value(true)
+                .requires("author"))
+            .arg(Arg::with_name("author")
+                .short("a")
+                .long("author")
+                .help("id of an author to merge")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1))
+            .arg(Arg::with_name("unmerge")
+                .long("unmerge")
+                .help("id of an author to separate from whoever it was merged into")
+                .takes_value(true)
+                .conflicts_with("into"))
+            .arg(dry_run::arg()),
+    ]
+}
+
+#[derive(Serialize)]
+struct OptOut<'a> {
+    email: &'a str,
+    visible: bool,
+}
+
+pub fn opt_out(ctx: &Context, matches: &ArgMatches) -> CommandResult {
+    use thanks::schema::authors::dsl::*;
+
+    let author_email = matches.value_of("email").unwrap();
+    let new_visible = matches.is_present("opt-in");
+
+    let author = diesel::update(authors.filter(email.eq(author_email)))
+        .set(opted_out.eq(!new_visible))
+        .get_result::<Author>(&ctx.conn)
+        .optional()?
+        .ok_or_else(|| Error::NotFound(format!("No author has the email {}", author_email)))?;
+
+    // merged authors are shown under the canonical one, so that's who opts
+    // out, unless someone else merged into them still wants to be hidden
+    let canonical = thanks::authors::canonical_id(&ctx.conn, author.id);
+    thanks::authors::update_visibility(&ctx.conn, author.id)?;
+    if canonical != author.id {
+        thanks::authors::update_visibility(&ctx.conn, canonical)?;
+    }
+
+    let text = if new_visible {
+        format!("Opted-in author with email: {}", author_email)
+    } else {
+        format!("Opted-out author with email: {}", author_email)
+    };
+    ctx.out.print(&text, &OptOut { email: author_email, visible: new_visible });
+
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct Alias<'a> {
+    canonical: i32,
+    id: i32,
+    name: &'a str,
+    email: &'a str,
+}
+
+pub fn merge_authors(ctx: &Context, matches: &ArgMatches) -> CommandResult {
+    if let Some(alias) = matches.value_of("unmerge") {
+        let alias = parse_id(alias)?;
+
+        if thanks::authors::unmerge(&ctx.conn, alias)? {
+            info!(ctx.log, "Unmerged author {}", alias);
+            return Ok(());
+        } else {
+            return Err(Error::NotFound(format!("Author {} wasn't merged into anyone", alias)));
+        }
+    }
+
+    let canonical = match matches.value_of("into") {
+        Some(canonical) => parse_id(canonical)?,
+        None => return Err(Error::Usage(String::from("Specify --into and --author, or --unmerge"))),
+    };
+
+    let mut failed = 0;
+    for alias in matches.values_of("author").unwrap() {
+        let alias = parse_id(alias)?;
+
+        match thanks::authors::merge(&ctx.conn, canonical, alias) {
+            Ok(merged_into) => info!(ctx.log, "Merged author {} into {}", alias, merged_into),
+            Err(e) => {
+                error!(ctx.log, "Couldn't merge author {}: {}", alias, e);
+                failed += 1;
+            }
+        }
+    }
+
+    let canonical = thanks::authors::canonical_id(&ctx.conn, canonical);
+    for alias in thanks::authors::aliases(&ctx.conn, canonical) {
+        let merged = Alias {
+            canonical: canonical,
+            id: alias.id,
+            name: &alias.name,
+            email: &alias.email,
+        };
+        ctx.out.print(&format!("Author {} is now also {} <{}> ({})", canonical, alias.name, alias.email, alias.id),
+                      &merged);
+    }
+
+    if failed > 0 {
+        return Err(Error::Failed(format!("{} authors couldn't be merged", failed)));
+    }
+
+    Ok(())
+}
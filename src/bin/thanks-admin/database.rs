@@ -0,0 +1,151 @@
+//! Commands that look after the database as a whole.
+
+use {dry_run, CommandResult, Context, Error};
+
+use clap::{App, Arg, ArgGroup, ArgMatches, SubCommand};
+
+use diesel;
+use diesel::prelude::*;
+
+use thanks::models::{Maintenance, Project, Release};
+
+pub fn commands() -> Vec<App<'static, 'static>> {
+    vec![
+        SubCommand::with_name("maintenance")
+            .about("let people know the db is re-building")
+            .arg(Arg::with_name("on")
+                .long("on")
+                .help("turn maintenance on"))
+            .arg(Arg::with_name("off")
+                .long("off")
+                .help("turn maintenance off"))
+            .group(ArgGroup::with_name("state")
+                .args(&["on", "off"])
+                .required(true))
+            .arg(dry_run::arg()),
+        SubCommand::with_name("the-big-red-button")
+            .about("annihilate --project, or everything with --all")
+            .arg(Arg::with_name("all")
+                .long("all")
+                .help("remove everything from the database"))
+            .arg(dry_run::arg()),
+    ]
+}
+
+#[derive(Serialize)]
+struct MaintenanceState {
+    enabled: bool,
+}
+
+pub fn maintenance(ctx: &Context, matches: &ArgMatches) -> CommandResult {
+    use thanks::schema::maintenances::dsl::*;
+
+    let on = matches.is_present("on");
+
+    let model = maintenances.find(1).first::<Maintenance>(&ctx.conn)?;
+    diesel::update(&model)
+        .set(enabled.eq(on))
+        .execute(&ctx.conn)?;
+
+    let text = if on { "maintenance turned on" } else { "maintenance turned off" };
+    ctx.out.print(text, &MaintenanceState { enabled: on });
+
+    Ok(())
+}
+
+pub fn the_big_red_button(ctx: &Context, matches: &ArgMatches) -> CommandResult {
+    match (matches.is_present("all"), ctx.project_name) {
+        (true, None) => delete_whole_db(ctx),
+        (false, Some(_)) => delete_projects_db(ctx, &ctx.project()?),
+        _ => Err(Error::Usage(String::from("Pass either --all or --project"))),
+    }
+}
+
+fn delete_projects_db(ctx: &Context, project: &Project) -> CommandResult {
+    use thanks::schema::releases::dsl::{releases, id as _release_id};
+    use thanks::schema::projects::dsl::{projects, id as _project_id};
+    use thanks::schema::commits::dsl::{commits, release_id};
+    use thanks::schema::authors::dsl::{authors, id as _author_id};
+    use thanks::schema::{github_syncs, repositories};
+    use diesel::expression::dsl::any;
+    use diesel::expression::dsl::sql;
+    use diesel::types::Bool;
+
+    let log = &ctx.log;
+    let connection = &ctx.conn;
+
+    let releases_to_delete = Release::belonging_to(project).load::<Release>(connection)?;
+    let release_names: Vec<&str> = releases_to_delete.iter().map(|release| release.version.as_str()).collect();
+    let release_ids: Vec<i32> = releases_to_delete.iter().map(|release| release.id).collect();
+    info!(log, "Deleting project {} with release names: {:?}", project.name, release_names);
+
+    info!(log, "Deleting commits");
+    diesel::delete(commits.filter(release_id.eq(any(&release_ids))))
+        .execute(connection)?;
+
+    // authors are kept while another project still credits them, either as
+    // a co-author or through an alias
+    info!(log, "Deleting authors");
+    // we can rewrite the raw sql to a query builder
+    // when diesel fixes this https://github.com/diesel-rs/diesel/issues/621
+    let author_ids_to_delete = authors.left_outer_join(commits)
+        .filter(sql::<Bool>("commits.sha IS NULL"))
+        .filter(sql::<Bool>("NOT EXISTS (SELECT 1 FROM co_authors WHERE co_authors.author_id = authors.id)"))
+        .filter(sql::<Bool>("NOT EXISTS (SELECT 1 FROM author_aliases \
+                             WHERE author_aliases.alias_id = authors.id \
+                             OR author_aliases.canonical_id = authors.id)"))
+        .select(_author_id);
+    diesel::delete(authors.filter(_author_id.eq(any(author_ids_to_delete))))
+        .execute(connection)?;
+
+    info!(log, "Deleting releases");
+    diesel::delete(releases.filter(_release_id.eq(any(&release_ids))))
+        .execute(connection)?;
+
+    // otherwise the next sync would pick up where this one left off, and
+    // miss the commits that were just deleted
+    info!(log, "Deleting GitHub syncs");
+    let repository_names = repositories::table
+        .filter(repositories::project_id.eq(project.id))
+        .select(repositories::github_name);
+    diesel::delete(github_syncs::table.filter(github_syncs::repository.eq(any(repository_names))))
+        .execute(connection)?;
+
+    info!(log, "Deleting project");
+    diesel::delete(projects.filter(_project_id.eq(project.id)))
+        .execute(connection)?;
+
+    info!(log, "Done.");
+
+    Ok(())
+}
+
+fn delete_whole_db(ctx: &Context) -> CommandResult {
+    use thanks::schema::releases::dsl::*;
+    use thanks::schema::commits::dsl::*;
+    use thanks::schema::projects::dsl::*;
+    use thanks::schema::authors::dsl::*;
+    use thanks::schema::github_syncs::dsl::*;
+
+    let log = &ctx.log;
+    let connection = &ctx.conn;
+
+    info!(log, "Deleting commits");
+    diesel::delete(commits).execute(connection)?;
+
+    info!(log, "Deleting authors");
+    diesel::delete(authors).execute(connection)?;
+
+    info!(log, "Deleting releases");
+    diesel::delete(releases).execute(connection)?;
+
+    info!(log, "Deleting projects");
+    diesel::delete(projects).execute(connection)?;
+
+    info!(log, "Deleting GitHub syncs");
+    diesel::delete(github_syncs).execute(connection)?;
+
+    info!(log, "Done.");
+
+    Ok(())
+}
@@ -0,0 +1,115 @@
+//! Running a command without keeping what it did.
+//!
+//! The command runs as usual inside a transaction, which is rolled back once
+//! postgres has told us how many rows it changed in each table. Only those
+//! counts are printed, not what the command would have printed itself.
+
+use {CommandResult, Context, Error};
+
+use clap::{Arg, ArgMatches};
+
+use diesel;
+use diesel::prelude::*;
+use diesel::expression::dsl::sql;
+use diesel::pg::PgConnection;
+use diesel::types::{BigInt, Text};
+
+/// the `--dry-run` flag of commands that change the database
+pub fn arg() -> Arg<'static, 'static> {
+    Arg::with_name("dry-run")
+        .long("dry-run")
+        .help("only print how many rows would change")
+}
+
+/// the rows a command changed in one table
+#[derive(Serialize)]
+struct TableChanges {
+    table: String,
+    inserted: i64,
+    updated: i64,
+    deleted: i64,
+}
+
+#[derive(Serialize)]
+struct Summary<'a> {
+    dry_run: bool,
+    inserted: i64,
+    updated: i64,
+    deleted: i64,
+    tables: &'a [TableChanges],
+}
+
+/// how the transaction of a dry run ended
+enum Outcome {
+    RolledBack(Vec<TableChanges>),
+    Failed(Error),
+}
+
+impl From<diesel::result::Error> for Outcome {
+    fn from(e: diesel::result::Error) -> Outcome {
+        Outcome::Failed(e.into())
+    }
+}
+
+/// runs `command`, rolling it back if `--dry-run` was given
+pub fn run<F>(ctx: &Context, matches: &ArgMatches, command: F) -> CommandResult
+    where F: FnOnce() -> CommandResult
+{
+    if !matches.is_present("dry-run") {
+        return command();
+    }
+
+    let outcome = ctx.conn.transaction::<(), Outcome, _>(|| {
+        ctx.out.quietly(command).map_err(Outcome::Failed)?;
+
+        // returning an error is what rolls the transaction back
+        Err(Outcome::RolledBack(changes(&ctx.conn)?))
+    });
+
+    match outcome {
+        Err(Outcome::RolledBack(tables)) => {
+            print(ctx, &tables);
+            Ok(())
+        }
+        Err(Outcome::Failed(e)) => Err(e),
+        Ok(()) => unreachable!(),
+    }
+}
+
+/// the rows changed so far in this transaction, by table
+fn changes(conn: &PgConnection) -> QueryResult<Vec<TableChanges>> {
+    let rows = sql::<(Text, BigInt, BigInt, BigInt)>(
+        "SELECT relname::text, n_tup_ins, n_tup_upd, n_tup_del \
+         FROM pg_stat_xact_user_tables \
+         WHERE n_tup_ins + n_tup_upd + n_tup_del > 0 \
+         ORDER BY relname")
+        .load::<(String, i64, i64, i64)>(conn)?;
+
+    Ok(rows.into_iter()
+        .map(|(table, inserted, updated, deleted)| TableChanges {
+            table: table,
+            inserted: inserted,
+            updated: updated,
+            deleted: deleted,
+        })
+        .collect())
+}
+
+fn print(ctx: &Context, tables: &[TableChanges]) {
+    let summary = Summary {
+        dry_run: true,
+        inserted: tables.iter().map(|t| t.inserted).sum(),
+        updated: tables.iter().map(|t| t.updated).sum(),
+        deleted: tables.iter().map(|t| t.deleted).sum(),
+        tables: tables,
+    };
+
+    let mut text = format!("Dry run, nothing was changed. Would have inserted {}, updated {} and deleted {} rows",
+                           summary.inserted, summary.updated, summary.deleted);
+    for table in tables {
+        text.push_str(&format!("\n  {}: {} inserted, {} updated, {} deleted",
+                               table.table, table.inserted, table.updated, table.deleted));
+    }
+
+    ctx.out.print(&text, &summary);
+}
@@ -0,0 +1,158 @@
+//! Rendering the whole site into static files.
+
+use {CommandResult, Context, Error};
+
+use clap::{App, Arg, ArgMatches, SubCommand};
+use slog::Logger;
+
+use handlebars::Handlebars;
+
+use thanks::dates::DateRange;
+use thanks::pages::{self, Page};
+
+use std::fmt::Display;
+use std::fs::{self, File};
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+
+pub fn commands() -> Vec<App<'static, 'static>> {
+    vec![
+        SubCommand::with_name("export")
+            .about("render the whole site into a directory of static files")
+            .arg(Arg::with_name("output")
+                .short("o")
+                .long("output")
+                .help("directory to write the site into")
+                .takes_value(true)
+                .required(true))
+            .arg(Arg::with_name("templates")
+                .long("templates")
+                .help("directory containing the templates")
+                .takes_value(true)
+                .default_value("templates"))
+            .arg(Arg::with_name("public")
+                .long("public")
+                .help("directory containing the static assets")
+                .takes_value(true)
+                .default_value("public")),
+    ]
+}
+
+pub fn export(ctx: &Context, matches: &ArgMatches) -> CommandResult {
+    let log = &ctx.log;
+    let connection = &ctx.conn;
+
+    let output = Path::new(matches.value_of("output").unwrap());
+    let templates = Path::new(matches.value_of("templates").unwrap());
+    let public = Path::new(matches.value_of("public").unwrap());
+
+    let handlebars = load_templates(templates)?;
+    let site = Site {
+        log: log,
+        handlebars: &handlebars,
+        output: output,
+    };
+
+    info!(log, "Copying {} to {}", public.display(), output.display());
+    copy_dir(public, output)?;
+
+    site.write("index", &["index.html"], &pages::index(connection))?;
+    site.write("about", &["about", "index.html"], &pages::about(connection))?;
+    site.write("all-time", &["all-time", "index.html"], &pages::organization_all_time(connection, &DateRange::all()))?;
+
+    for project in thanks::projects::all(connection) {
+        info!(log, "Exporting {}", project.name);
+        let url_path = project.url_path.as_str();
+
+        site.write("project", &[url_path, "index.html"], &pages::project(connection, &project))?;
+        site.write("all-time", &[url_path, "all-time", "index.html"], &pages::all_time(connection, &project, &DateRange::all()))?;
+        site.write("feed", &[url_path, "feed.xml"], &pages::feed(connection, &project))?;
+
+        for version in thanks::releases::versions(connection, &project) {
+            if let Some(page) = pages::release(connection, &project, &version, &DateRange::all()) {
+                site.write("release", &[url_path, version.as_str(), "index.html"], &page)?;
+            }
+        }
+
+        // everyone on the leaderboard has a profile
+        for score in thanks::ranked_scores(connection, &project, &DateRange::all()) {
+            if let Some(page) = pages::author(connection, &project, score.author_id) {
+                let id = score.author_id.to_string();
+                site.write("author", &[url_path, "authors", id.as_str(), "index.html"], &page)?;
+            }
+        }
+    }
+
+    info!(log, "Done!");
+
+    Ok(())
+}
+
+struct Site<'a> {
+    log: &'a Logger,
+    handlebars: &'a Handlebars,
+    output: &'a Path,
+}
+
+impl<'a> Site<'a> {
+    /// renders `page` through `template` into the file at `path`
+    ///
+    /// pages are written as `index.html` inside a directory named after
+    /// their route, so that static hosts serve them under the same urls
+    fn write(&self, template: &str, path: &[&str], page: &Page) -> CommandResult {
+        let path: PathBuf = path.iter().fold(self.output.to_path_buf(), |path, part| path.join(part));
+
+        let html = self.handlebars.render(template, page)
+            .map_err(|e| failed("Error rendering", &path, e))?;
+
+        fs::create_dir_all(path.parent().unwrap())
+            .map_err(|e| failed("Error creating the directory for", &path, e))?;
+        File::create(&path)
+            .and_then(|mut file| file.write_all(html.as_bytes()))
+            .map_err(|e| failed("Error writing", &path, e))?;
+
+        debug!(self.log, "Wrote {}", path.display());
+
+        Ok(())
+    }
+}
+
+/// registers every `.hbs` file in `dir` under its file name
+fn load_templates(dir: &Path) -> Result<Handlebars, Error> {
+    let mut handlebars = Handlebars::new();
+
+    for entry in fs::read_dir(dir).map_err(|e| failed("Error reading", dir, e))? {
+        let path = entry.map_err(|e| failed("Error reading", dir, e))?.path();
+
+        if path.extension().map_or(false, |ext| ext == "hbs") {
+            let name = path.file_stem().unwrap().to_string_lossy().into_owned();
+            handlebars.register_template_file(&name, &path)
+                .map_err(|e| failed("Error loading template", &path, e))?;
+        }
+    }
+
+    Ok(handlebars)
+}
+
+fn copy_dir(from: &Path, to: &Path) -> CommandResult {
+    fs::create_dir_all(to).map_err(|e| failed("Error creating", to, e))?;
+
+    for entry in fs::read_dir(from).map_err(|e| failed("Error reading", from, e))? {
+        let entry = entry.map_err(|e| failed("Error reading", from, e))?;
+        let target = to.join(entry.file_name());
+
+        if entry.path().is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else {
+            fs::copy(entry.path(), &target)
+                .map_err(|e| failed("Error copying", &entry.path(), e))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// an error about the file at `path`, which fails the export
+fn failed<E: Display>(doing: &str, path: &Path, e: E) -> Error {
+    Error::Failed(format!("{} {}: {}", doing, path.display(), e))
+}
@@ -0,0 +1,342 @@
+//! Every command for running the site, as subcommands of one binary.
+//!
+//! Commands exit with 0 when they succeed, 1 when something went wrong, 2
+//! when the command line doesn't make sense, and 3 when a project, release or
+//! author named on it doesn't exist.
+
+extern crate thanks;
+
+extern crate clap;
+
+extern crate diesel;
+
+extern crate dotenv;
+
+extern crate handlebars;
+
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_json;
+
+#[macro_use]
+extern crate slog;
+extern crate slog_term;
+
+mod authors;
+mod database;
+mod dry_run;
+mod export;
+mod projects;
+mod releases;
+
+use clap::{App, AppSettings, Arg, ArgMatches, ErrorKind, Shell, SubCommand};
+use slog::{DrainExt, Level, Logger};
+
+use diesel::prelude::*;
+use diesel::pg::PgConnection;
+
+use serde::Serialize;
+
+use thanks::models::Project;
+
+use std::cell::Cell;
+use std::env;
+use std::error;
+use std::fmt;
+use std::io::{self, Write};
+use std::panic::{self, AssertUnwindSafe};
+use std::process;
+
+const EXIT_FAILED: i32 = 1;
+const EXIT_USAGE: i32 = 2;
+const EXIT_NOT_FOUND: i32 = 3;
+
+/// why a command failed, which decides how it exits
+#[derive(Debug)]
+pub enum Error {
+    /// the command line doesn't make sense
+    Usage(String),
+    /// something named on the command line doesn't exist
+    NotFound(String),
+    /// anything else
+    Failed(String),
+}
+
+impl Error {
+    fn exit_code(&self) -> i32 {
+        match *self {
+            Error::Usage(_) => EXIT_USAGE,
+            Error::NotFound(_) => EXIT_NOT_FOUND,
+            Error::Failed(_) => EXIT_FAILED,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Usage(ref message) | Error::NotFound(ref message) | Error::Failed(ref message) => {
+                f.write_str(message)
+            }
+        }
+    }
+}
+
+impl From<Box<error::Error>> for Error {
+    fn from(e: Box<error::Error>) -> Error {
+        Error::Failed(e.to_string())
+    }
+}
+
+impl From<diesel::result::Error> for Error {
+    fn from(e: diesel::result::Error) -> Error {
+        Error::Failed(e.to_string())
+    }
+}
+
+pub type CommandResult = Result<(), Error>;
+
+/// what every command gets to work with
+pub struct Context<'a> {
+    pub log: Logger,
+    pub conn: PgConnection,
+    pub out: Output,
+    project_name: Option<&'a str>,
+}
+
+impl<'a> Context<'a> {
+    /// the project given with `--project`
+    pub fn project(&self) -> Result<Project, Error> {
+        let name = self.project_name()?;
+
+        thanks::projects::find_by_name(&self.conn, name)
+            .ok_or_else(|| Error::NotFound(format!("Unknown project {}", name)))
+    }
+
+    /// the name given with `--project`, for commands that create the project
+    pub fn project_name(&self) -> Result<&'a str, Error> {
+        self.project_name.ok_or_else(|| Error::Usage(String::from("Pass the project with --project")))
+    }
+}
+
+/// where commands print their results
+pub struct Output {
+    json: bool,
+    /// set while a dry run's command runs, since only its summary is printed
+    quiet: Cell<bool>,
+}
+
+impl Output {
+    /// prints a result, as `text` for people or as a line of JSON
+    pub fn print<T: Serialize>(&self, text: &str, value: &T) {
+        if self.quiet.get() {
+            return;
+        }
+
+        if self.json {
+            println!("{}", serde_json::to_string(value).expect("Error serializing output"));
+        } else {
+            println!("{}", text);
+        }
+    }
+
+    /// runs `f` without printing its results
+    pub fn quietly<T, F: FnOnce() -> T>(&self, f: F) -> T {
+        self.quiet.set(true);
+        let result = f();
+        self.quiet.set(false);
+
+        result
+    }
+}
+
+fn app() -> App<'static, 'static> {
+    App::new("thanks-admin")
+        .about("manage the projects, releases and authors the site thanks")
+        .version(env!("CARGO_PKG_VERSION"))
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .setting(AppSettings::VersionlessSubcommands)
+        .arg(Arg::with_name("database-url")
+            .long("database-url")
+            .help("the database to use, instead of DATABASE_URL")
+            .takes_value(true)
+            .global(true))
+        .arg(Arg::with_name("log-level")
+            .long("log-level")
+            .help("the least important messages to log")
+            .takes_value(true)
+            .possible_values(&["critical", "error", "warning", "info", "debug", "trace"])
+            .default_value("info")
+            .global(true))
+        .arg(Arg::with_name("json")
+            .long("json")
+            .help("print results as lines of JSON")
+            .global(true))
+        .arg(Arg::with_name("project")
+            .long("project")
+            .help("name of the project to work on")
+            .takes_value(true)
+            .global(true))
+        .subcommands(projects::commands())
+        .subcommands(releases::commands())
+        .subcommands(authors::commands())
+        .subcommands(database::commands())
+        .subcommands(export::commands())
+        .subcommand(SubCommand::with_name("completions")
+            .about("print a shell completion script")
+            .arg(Arg::with_name("shell")
+                .help("the shell to complete in")
+                .possible_values(&Shell::variants())
+                .required(true)))
+}
+
+fn main() {
+    let matches = match app().get_matches_safe() {
+        Ok(matches) => matches,
+        Err(ref e) if e.kind == ErrorKind::HelpDisplayed || e.kind == ErrorKind::VersionDisplayed => e.exit(),
+        Err(e) => {
+            writeln!(io::stderr(), "{}", e.message).unwrap();
+            process::exit(EXIT_USAGE);
+        }
+    };
+
+    if let ("completions", Some(matches)) = matches.subcommand() {
+        let shell = matches.value_of("shell").unwrap().parse::<Shell>().unwrap();
+        app().gen_completions_to("thanks-admin", shell, &mut io::stdout());
+        return;
+    }
+
+    let level = match global(&matches, "log-level").unwrap() {
+        "critical" => Level::Critical,
+        "error" => Level::Error,
+        "warning" => Level::Warning,
+        "debug" => Level::Debug,
+        "trace" => Level::Trace,
+        _ => Level::Info,
+    };
+    let drain = slog::level_filter(level, slog_term::streamer().full().build());
+    let log = Logger::root(drain.fuse(), o!("version" => env!("CARGO_PKG_VERSION")));
+
+    let conn = match connect(global(&matches, "database-url")) {
+        Ok(conn) => conn,
+        Err(e) => {
+            error!(log, "{}", e);
+            process::exit(e.exit_code());
+        }
+    };
+
+    let ctx = Context {
+        log: log.clone(),
+        conn: conn,
+        out: Output {
+            json: global(&matches, "json").is_some(),
+            quiet: Cell::new(false),
+        },
+        project_name: global(&matches, "project"),
+    };
+
+    // the library still panics when git fails or the pages can't be loaded,
+    // which is as much a failure as any other, so it's reported the same way
+    let hook_log = log.clone();
+    panic::set_hook(Box::new(move |info| {
+        let message = match info.payload().downcast_ref::<&str>() {
+            Some(message) => message.to_string(),
+            None => match info.payload().downcast_ref::<String>() {
+                Some(message) => message.clone(),
+                None => String::from("unknown error"),
+            },
+        };
+
+        match info.location() {
+            Some(location) => error!(hook_log, "{} ({}:{})", message, location.file(), location.line()),
+            None => error!(hook_log, "{}", message),
+        }
+    }));
+
+    let result = panic::catch_unwind(AssertUnwindSafe(|| run(&ctx, &matches)));
+
+    let code = match result {
+        Ok(Ok(())) => 0,
+        Ok(Err(e)) => {
+            error!(log, "{}", e);
+            e.exit_code()
+        }
+        // the panic hook already logged why
+        Err(_) => EXIT_FAILED,
+    };
+
+    process::exit(code);
+}
+
+fn run(ctx: &Context, matches: &ArgMatches) -> CommandResult {
+    match matches.subcommand() {
+        ("populate", Some(matches)) => dry_run::run(ctx, matches, || projects::populate(ctx, matches)),
+        ("add-repository", Some(matches)) => dry_run::run(ctx, matches, || projects::add_repository(ctx, matches)),
+        ("sync", Some(matches)) => projects::sync(ctx, matches),
+        ("update-commit-db", Some(matches)) => {
+            dry_run::run(ctx, matches, || projects::update_commit_db(ctx, matches))
+        }
+        ("webhook-secret", Some(matches)) => dry_run::run(ctx, matches, || projects::webhook_secret(ctx, matches)),
+        ("new-release", Some(matches)) => dry_run::run(ctx, matches, || releases::new_release(ctx, matches)),
+        ("recompute-releases", Some(matches)) => {
+            dry_run::run(ctx, matches, || releases::recompute_releases(ctx, matches))
+        }
+        ("visible", Some(matches)) => dry_run::run(ctx, matches, || releases::visible(ctx, matches)),
+        ("release-notes", Some(matches)) => releases::release_notes(ctx, matches),
+        ("opt-out", Some(matches)) => dry_run::run(ctx, matches, || authors::opt_out(ctx, matches)),
+        ("merge-authors", Some(matches)) => dry_run::run(ctx, matches, || authors::merge_authors(ctx, matches)),
+        ("maintenance", Some(matches)) => dry_run::run(ctx, matches, || database::maintenance(ctx, matches)),
+        ("the-big-red-button", Some(matches)) => {
+            dry_run::run(ctx, matches, || database::the_big_red_button(ctx, matches))
+        }
+        ("export", Some(matches)) => export::export(ctx, matches),
+        (name, _) => Err(Error::Usage(format!("Unknown command {}", name))),
+    }
+}
+
+/// the value of a global option, which may come before or after the command
+fn global<'a>(matches: &'a ArgMatches, name: &str) -> Option<&'a str> {
+    let value = |matches: &'a ArgMatches| {
+        if matches.is_present(name) {
+            Some(matches.value_of(name).unwrap_or(""))
+        } else {
+            matches.value_of(name)
+        }
+    };
+
+    matches.subcommand().1.and_then(|matches| value(matches)).or_else(|| value(matches))
+}
+
+fn connect(database_url: Option<&str>) -> Result<PgConnection, Error> {
+    dotenv::dotenv().ok();
+
+    let database_url = match database_url {
+        Some(database_url) => database_url.to_string(),
+        None => env::var("DATABASE_URL")
+            .map_err(|_| Error::Usage(String::from("Pass --database-url or set DATABASE_URL")))?,
+    };
+
+    PgConnection::establish(&database_url)
+        .map_err(|e| Error::Failed(format!("Error connecting to {}: {}", database_url, e)))
+}
+
+/// parses a number given on the command line
+pub fn parse_id(id: &str) -> Result<i32, Error> {
+    id.parse().map_err(|_| Error::Usage(format!("{} isn't an id", id)))
+}
+
+/// finds a release of the project given with `--project`
+pub fn find_release(ctx: &Context, project: &Project, version: &str) -> Result<thanks::models::Release, Error> {
+    thanks::releases::find(&ctx.conn, project, version)
+        .ok_or_else(|| Error::NotFound(format!("{} has no release {}", project.name, version)))
+}
+
+/// pairs repositories with the values given for them, like `--path`
+pub fn by_repository(ctx: &Context, project: &Project, values: Option<clap::Values>)
+    -> Result<Vec<(thanks::models::Repository, String)>, Error>
+{
+    let values: Vec<&str> = values.map(|values| values.collect()).unwrap_or_default();
+
+    thanks::repositories::by_name(&ctx.conn, project, &values).map_err(Error::Usage)
+}
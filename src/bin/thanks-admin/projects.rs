@@ -0,0 +1,431 @@
+//! Commands that set up projects and bring in their commits.
+
+use {dry_run, find_release, CommandResult, Context, Error};
+
+use clap::{App, Arg, ArgMatches, SubCommand};
+
+use thanks::config::Config;
+use thanks::github::Client;
+use thanks::mailmap::Mailmap;
+use thanks::models::Release;
+
+pub fn commands() -> Vec<App<'static, 'static>> {
+    vec![
+        SubCommand::with_name("populate")
+            .about("create a project from a checkout of its repository, or pick up where it left off")
+            .arg(Arg::with_name("filepath")
+                .short("p")
+                .long("path")
+                .help("filepath of the source code")
+                .takes_value(true)
+                .required(true))
+            .arg(Arg::with_name("url_path")
+                .short("u")
+                .long("url")
+                .help("url path for this project")
+                .takes_value(true)
+                .required(true))
+            .arg(Arg::with_name("github_name")
+                .short("g")
+                .long("github")
+                .help("GitHub name of the project's repository, like rust-lang/rust")
+                .takes_value(true)
+                .required(true))
+            .args(&repository_args())
+            .arg(dry_run::arg()),
+        SubCommand::with_name("add-repository")
+            .about("add another repository to a project, and credit its commits in the project's releases")
+            .arg(Arg::with_name("github_name")
+                .short("g")
+                .long("github")
+                .help("GitHub name of the repository, like rust-lang/cargo")
+                .takes_value(true)
+                .required(true))
+            .arg(Arg::with_name("filepath")
+                .short("p")
+                .long("path")
+                .help("filepath of the repository's source code")
+                .takes_value(true)
+                .required(true))
+            .args(&repository_args())
+            .arg(Arg::with_name("tag")
+                .short("t")
+                .long("tag")
+                .help("the tag of a release that doesn't follow the tag pattern, like 1.19.0=0.20.0")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1))
+            .arg(dry_run::arg()),
+        SubCommand::with_name("sync")
+            .about("make the projects in the database match the config file")
+            .arg(Arg::with_name("config")
+                .short("c")
+                .long("config")
+                .help("the config file")
+                .takes_value(true)
+                .default_value("thanks.toml"))
+            .arg(Arg::with_name("dry-run")
+                .long("dry-run")
+                .help("only print what would change")),
+        SubCommand::with_name("update-commit-db")
+            .about("fetch new commits from GitHub, for every project or just --project")
+            .arg(Arg::with_name("requested")
+                .long("requested")
+                .help("only fetch the repositories a webhook asked to, after a push too big to include every commit"))
+            .arg(dry_run::arg()),
+        SubCommand::with_name("webhook-secret")
+            .about("set the secret a repository's GitHub webhook is signed with")
+            .arg(Arg::with_name("github_name")
+                .short("g")
+                .long("github")
+                .help("GitHub name of the repository, like rust-lang/cargo")
+                .takes_value(true)
+                .required(true))
+            .arg(Arg::with_name("secret")
+                .short("s")
+                .long("secret")
+                .help("the secret entered in GitHub's webhook settings")
+                .takes_value(true)
+                .required_unless("remove"))
+            .arg(Arg::with_name("remove")
+                .long("remove")
+                .help("Use this to refuse the repository's webhook deliveries again")
+                .conflicts_with("secret"))
+            .arg(dry_run::arg()),
+    ]
+}
+
+/// the settings of a new repository
+fn repository_args() -> Vec<Arg<'static, 'static>> {
+    vec![
+        Arg::with_name("tag_pattern")
+            .long("tag-pattern")
+            .help("how release tags are named, with {version} standing for the version")
+            .takes_value(true)
+            .default_value("{version}"),
+        Arg::with_name("mailmap")
+            .short("m")
+            .long("mailmap")
+            .help("path or url of the repository's mailmap, if it isn't the .mailmap in the repo")
+            .takes_value(true),
+        Arg::with_name("branch")
+            .short("b")
+            .long("branch")
+            .help("the branch unreleased commits are on")
+            .takes_value(true)
+            .default_value("master"),
+    ]
+}
+
+fn tag_pattern<'a>(matches: &'a ArgMatches) -> Result<&'a str, Error> {
+    let tag_pattern = matches.value_of("tag_pattern").unwrap();
+
+    if tag_pattern.contains("{version}") {
+        Ok(tag_pattern)
+    } else {
+        Err(Error::Usage(String::from("The tag pattern must contain {version}")))
+    }
+}
+
+#[derive(Serialize)]
+struct Populated<'a> {
+    project: &'a str,
+    releases_added: usize,
+    releases_present: usize,
+    commits_added: usize,
+    commits_present: usize,
+    releases_assigned: usize,
+    releases_skipped: usize,
+}
+
+pub fn populate(ctx: &Context, matches: &ArgMatches) -> CommandResult {
+    let log = &ctx.log;
+    let project_name = ctx.project_name()?;
+    let path = matches.value_of("filepath").unwrap();
+    let url_path = matches.value_of("url_path").unwrap();
+    let github_name = matches.value_of("github_name").unwrap();
+    let mailmap_source = matches.value_of("mailmap");
+    let tag_pattern = tag_pattern(matches)?;
+    let branch = matches.value_of("branch").unwrap();
+
+    // create project and its first repository; more can be added with
+    // add-repository. if sync already created them from the config, or an
+    // earlier run was interrupted, they're picked up again and the settings
+    // in the database win
+    let project = match thanks::projects::find_by_name(&ctx.conn, project_name) {
+        Some(project) => {
+            info!(log, "Using the existing project {}", project.name);
+            project
+        }
+        None => thanks::projects::create(&ctx.conn, project_name, url_path, github_name)?,
+    };
+    let repository = match thanks::repositories::find_by_github_name(&ctx.conn, github_name) {
+        Some(ref repository) if repository.project_id != project.id => {
+            return Err(Error::Usage(format!("{} already belongs to another project", repository.github_name)));
+        }
+        Some(repository) => {
+            info!(log, "Using the existing repository {}", repository.github_name);
+            repository
+        }
+        None => thanks::repositories::create(&ctx.conn, &project, github_name, tag_pattern, mailmap_source, branch)?,
+    };
+    let mailmap = Mailmap::for_checkout(&repository, path)?;
+
+    // find releases
+    let releases = thanks::releases::from_tags(&repository, &thanks::git::tags(path));
+    info!(log, "Found {} tagged releases", releases.len() - 1);
+
+    // the oldest release has every commit assigned to it by default, which
+    // is master if there are no tags at all
+    let mut releases_added = 0;
+    let mut project_releases: Vec<Release> = Vec::new();
+    for release in releases.iter() {
+        let project_release = match thanks::releases::find(&ctx.conn, &project, &release.version) {
+            Some(existing) => existing,
+            None => {
+                releases_added += 1;
+                thanks::releases::create(&ctx.conn, &release.version, project.id, true)?
+            }
+        };
+        project_releases.push(project_release);
+    }
+    let first_release = &project_releases[0];
+
+    // create most commits
+    //
+    // backported commits aren't on master, so they're created when their
+    // release is assigned below
+    let git_log = thanks::git::log(path, &[repository.git_ref("master").as_str()], &mailmap);
+    let total_commits = git_log.len();
+    info!(log, "Found {} commits", total_commits);
+
+    let existing = thanks::commits::existing(&ctx.conn, git_log.iter().map(|entry| entry.sha.as_str()).collect())?;
+    let new_commits: Vec<_> = git_log.into_iter()
+        .filter(|entry| !existing.contains(&entry.sha))
+        .collect();
+
+    // We tag all commits initially to the first release. Each release will
+    // set this properly below.
+    let commits_added = thanks::commits::import(&ctx.conn, log, first_release, &new_commits)?;
+
+    // assign commits to their release, skipping the ones an earlier run
+    // finished. master moves, so it's always assigned again.
+    let mut releases_assigned = 0;
+    for (release, project_release) in releases.iter().zip(project_releases.iter()) {
+        if release.version != "master" && thanks::releases::is_assigned(&ctx.conn, &repository, project_release)? {
+            debug!(log, "Commits of {} were already assigned, skipping", release.version);
+            continue;
+        }
+
+        thanks::releases::assign_commits(&ctx.conn, log, &release.version, &repository, path, &mailmap)?;
+        releases_assigned += 1;
+    }
+
+    let populated = Populated {
+        project: &project.name,
+        releases_added: releases_added,
+        releases_present: releases.len() - releases_added,
+        commits_added: commits_added,
+        commits_present: total_commits - commits_added,
+        releases_assigned: releases_assigned,
+        releases_skipped: releases.len() - releases_assigned,
+    };
+    ctx.out.print(&format!("Releases: {} added, {} already present\n\
+                            Commits: {} added, {} already present\n\
+                            Assigned the commits of {} releases, {} were already done",
+                           populated.releases_added, populated.releases_present,
+                           populated.commits_added, populated.commits_present,
+                           populated.releases_assigned, populated.releases_skipped),
+                  &populated);
+
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct RepositoryAdded<'a> {
+    project: &'a str,
+    repository: &'a str,
+    commits_added: usize,
+    commits_moved: usize,
+}
+
+pub fn add_repository(ctx: &Context, matches: &ArgMatches) -> CommandResult {
+    let log = &ctx.log;
+    let project = ctx.project()?;
+    let github_name = matches.value_of("github_name").unwrap();
+    let path = matches.value_of("filepath").unwrap();
+    let mailmap_source = matches.value_of("mailmap");
+    let tag_pattern = tag_pattern(matches)?;
+    let branch = matches.value_of("branch").unwrap();
+
+    // sync may have created it from the config already, without commits
+    let existing = thanks::repositories::find_by_github_name(&ctx.conn, github_name);
+    if let Some(ref repository) = existing {
+        if repository.project_id != project.id {
+            return Err(Error::Usage(format!("{} was already added to another project", github_name)));
+        }
+    }
+
+    // look the releases up before creating anything, so a typo doesn't leave
+    // a repository behind
+    let mut tags: Vec<(Release, &str)> = Vec::new();
+    for tag in matches.values_of("tag").map(|tags| tags.collect()).unwrap_or_default() {
+        let mut parts = tag.splitn(2, '=');
+        match (parts.next(), parts.next()) {
+            (Some(version), Some(tag)) => tags.push((find_release(ctx, &project, version)?, tag)),
+            _ => return Err(Error::Usage(format!("Tags look like VERSION=TAG, not {}", tag))),
+        }
+    }
+
+    // read the checkout before saving anything, so a wrong path or branch
+    // fails without leaving a repository behind
+    let mailmap = match existing {
+        Some(ref repository) => Mailmap::for_checkout(repository, path)?,
+        None => Mailmap::load_or_checkout(mailmap_source, path)?,
+    };
+    let git_log = {
+        let master_branch = existing.as_ref().map_or(branch, |repository| repository.default_branch.as_str());
+        thanks::git::log(path, &[master_branch], &mailmap)
+    };
+    let master = thanks::releases::master(&ctx.conn, &project)?;
+
+    let (repository, added, reassignments) = ctx.conn.transaction::<_, Error, _>(|| {
+        let repository = match existing {
+            Some(repository) => {
+                info!(log, "Using the existing repository {}", repository.github_name);
+                repository
+            }
+            None => {
+                let repository = thanks::repositories::create(&ctx.conn, &project, github_name, tag_pattern,
+                                                              mailmap_source, branch)?;
+                info!(log, "Added {} to {}", repository.github_name, project.name);
+                repository
+            }
+        };
+
+        for &(ref release, tag) in tags.iter() {
+            thanks::repositories::set_tag(&ctx.conn, &repository, release, tag)?;
+            info!(log, "Tag of {}: {}", release.version, tag);
+        }
+
+        // every commit starts out in master, and is moved to the release it
+        // shipped in below
+        //
+        // backported commits aren't on master, so they're created when their
+        // release is assigned
+        let added = thanks::commits::import(&ctx.conn, log, &master, &git_log)?;
+
+        let reassignments = thanks::releases::recompute(&ctx.conn, log, &repository, path, &mailmap)?;
+
+        Ok((repository, added, reassignments))
+    })?;
+
+    let result = RepositoryAdded {
+        project: &project.name,
+        repository: &repository.github_name,
+        commits_added: added,
+        commits_moved: reassignments.len(),
+    };
+    ctx.out.print(&format!("Added {} of {} commits, and assigned {} to earlier releases",
+                           added, git_log.len(), reassignments.len()),
+                  &result);
+
+    Ok(())
+}
+
+pub fn sync(ctx: &Context, matches: &ArgMatches) -> CommandResult {
+    let config = Config::load(matches.value_of("config").unwrap()).map_err(Error::Usage)?;
+
+    let plan = thanks::sync::plan(&ctx.conn, &config);
+
+    for warning in plan.warnings.iter() {
+        warn!(ctx.log, "{}", warning);
+    }
+
+    for change in plan.changes.iter() {
+        ctx.out.print(&change.to_string(), change);
+    }
+
+    if plan.changes.is_empty() {
+        info!(ctx.log, "Nothing to change");
+        return Ok(());
+    }
+
+    if matches.is_present("dry-run") {
+        info!(ctx.log, "Would make {} changes", plan.changes.len());
+        return Ok(());
+    }
+
+    thanks::sync::apply(&ctx.conn, &plan.changes)
+        .map_err(|e| Error::Failed(format!("Error syncing, nothing was changed: {}", e)))?;
+    info!(ctx.log, "Made {} changes", plan.changes.len());
+
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct Updated<'a> {
+    project: &'a str,
+    repository: &'a str,
+    added: usize,
+}
+
+pub fn update_commit_db(ctx: &Context, matches: &ArgMatches) -> CommandResult {
+    let client = Client::from_env(&ctx.log)?;
+
+    let projects = match ctx.project_name {
+        Some(_) => vec![ctx.project()?],
+        None => thanks::projects::all(&ctx.conn),
+    };
+
+    let mut failed = 0;
+    for project in projects.iter() {
+        info!(ctx.log, "Updating {}", project.name);
+
+        for repository in thanks::repositories::all(&ctx.conn, project) {
+            if matches.is_present("requested") && repository.sync_requested_at.is_none() {
+                continue;
+            }
+
+            // one repository failing shouldn't keep the others from updating
+            match thanks::github::sync(&ctx.conn, &ctx.log, &client, project, &repository) {
+                Ok(added) => {
+                    let updated = Updated {
+                        project: &project.name,
+                        repository: &repository.github_name,
+                        added: added,
+                    };
+                    ctx.out.print(&format!("Added {} commits from {} to {}", added, repository.github_name, project.name),
+                                  &updated);
+                }
+                Err(e) => {
+                    error!(ctx.log, "Error updating {} from {}: {}", project.name, repository.github_name, e);
+                    failed += 1;
+                }
+            }
+        }
+    }
+
+    if failed > 0 {
+        return Err(Error::Failed(format!("{} repositories failed to update", failed)));
+    }
+
+    Ok(())
+}
+
+pub fn webhook_secret(ctx: &Context, matches: &ArgMatches) -> CommandResult {
+    let github_name = matches.value_of("github_name").unwrap();
+    let secret = matches.value_of("secret");
+
+    let repository = thanks::repositories::find_by_github_name(&ctx.conn, github_name)
+        .ok_or_else(|| Error::NotFound(format!("Unknown repository {}", github_name)))?;
+
+    thanks::repositories::set_webhook_secret(&ctx.conn, &repository, secret)?;
+
+    match secret {
+        Some(_) => info!(ctx.log, "Set the webhook secret of {}", repository.github_name),
+        None => info!(ctx.log, "Removed the webhook secret of {}", repository.github_name),
+    }
+
+    Ok(())
+}
@@ -0,0 +1,352 @@
+//! Commands that create releases and decide which commits are in them.
+
+use {by_repository, dry_run, find_release, CommandResult, Context, Error};
+
+use clap::{App, Arg, ArgMatches, SubCommand};
+
+use thanks::dates::DateRange;
+use thanks::mailmap::Mailmap;
+use thanks::models::Project;
+use thanks::releases::{Contributor, Reassignment};
+
+pub fn commands() -> Vec<App<'static, 'static>> {
+    vec![
+        SubCommand::with_name("new-release")
+            .about("create a new release")
+            .arg(path_arg())
+            .arg(Arg::with_name("version")
+                .short("v")
+                .long("version")
+                .help("new version number")
+                .takes_value(true)
+                .required(true))
+            .arg(Arg::with_name("previous")
+                .long("previous")
+                .help("the release this one follows, if it doesn't follow every earlier release")
+                .takes_value(true))
+            .arg(Arg::with_name("tag")
+                .short("t")
+                .long("tag")
+                .help("the release's tag in a repository, if it doesn't follow the tag pattern, like rust-lang/cargo=0.20.0")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1))
+            .arg(dry_run::arg()),
+        SubCommand::with_name("recompute-releases")
+            .about("assign every commit of a project to its release again")
+            .arg(path_arg())
+            .arg(Arg::with_name("release")
+                .short("r")
+                .long("release")
+                .help("release to override the previous release of, before recomputing")
+                .takes_value(true))
+            .arg(Arg::with_name("previous")
+                .long("previous")
+                .help("the release it follows")
+                .takes_value(true)
+                .requires("release"))
+            .arg(Arg::with_name("no-previous")
+                .long("no-previous")
+                .help("make it follow every earlier release again")
+                .requires("release")
+                .conflicts_with("previous"))
+            .arg(dry_run::arg()),
+        SubCommand::with_name("visible")
+            .about("mark a release as visible")
+            .arg(Arg::with_name("version")
+                .short("v")
+                .long("version")
+                .help("version of the release")
+                .takes_value(true)
+                .required(true))
+            .arg(Arg::with_name("hide")
+                .long("hide")
+                .help("Use this to mark the release as hidden"))
+            .arg(dry_run::arg()),
+        SubCommand::with_name("release-notes")
+            .about("print the thanks section for a release announcement")
+            .arg(Arg::with_name("version")
+                .short("v")
+                .long("version")
+                .help("version of the release")
+                .takes_value(true)
+                .required(true))
+            .arg(Arg::with_name("format")
+                .short("f")
+                .long("format")
+                .help("output format")
+                .takes_value(true)
+                .possible_values(&["markdown", "html", "text"])
+                .default_value("markdown")),
+    ]
+}
+
+fn path_arg() -> Arg<'static, 'static> {
+    Arg::with_name("filepath")
+        .short("p")
+        .long("path")
+        .help("filepath of a repository's source code, like rust-lang/cargo=~/src/cargo")
+        .takes_value(true)
+        .multiple(true)
+        .number_of_values(1)
+        .required(true)
+}
+
+#[derive(Serialize)]
+struct NewRelease<'a> {
+    project: &'a str,
+    version: &'a str,
+    previous: Option<&'a str>,
+}
+
+pub fn new_release(ctx: &Context, matches: &ArgMatches) -> CommandResult {
+    let log = &ctx.log;
+    let project = ctx.project()?;
+    let new_release_name = matches.value_of("version").unwrap();
+
+    let checkouts = by_repository(ctx, &project, matches.values_of("filepath"))?;
+    let tags = by_repository(ctx, &project, matches.values_of("tag"))?;
+
+    for repository in thanks::repositories::all(&ctx.conn, &project) {
+        match checkouts.iter().find(|&&(ref r, _)| r.id == repository.id) {
+            Some(&(_, ref path)) => info!(log, "Path to {} repo: {}", repository.github_name, path),
+            None => warn!(log, "No path to {} given, so none of its commits will be in {}",
+                          repository.github_name, new_release_name),
+        }
+    }
+
+    if thanks::releases::find(&ctx.conn, &project, new_release_name).is_some() {
+        return Err(Error::Usage(format!("Release {} already exists", new_release_name)));
+    }
+
+    let previous = matches.value_of("previous");
+    if let Some(previous) = previous {
+        find_release(ctx, &project, previous)?;
+    }
+
+    info!(log, "Creating new release: {}", new_release_name);
+    let new_release = thanks::releases::create(&ctx.conn, new_release_name, project.id, true)?;
+
+    if let Some(previous) = previous {
+        thanks::releases::set_previous(&ctx.conn, &new_release, Some(previous))?;
+        info!(log, "Previous release: {}", previous);
+    }
+
+    for &(ref repository, ref tag) in tags.iter() {
+        thanks::repositories::set_tag(&ctx.conn, repository, &new_release, tag)?;
+        info!(log, "Tag in {}: {}", repository.github_name, tag);
+    }
+
+    info!(log, "Assigning commits for {}", new_release.version);
+    for &(ref repository, ref path) in checkouts.iter() {
+        let mailmap = Mailmap::for_checkout(repository, path)?;
+        thanks::releases::assign_commits(&ctx.conn, log, &new_release.version, repository, path, &mailmap)?;
+    }
+
+    thanks::releases::publish(&ctx.conn, &new_release)?;
+
+    let created = NewRelease {
+        project: &project.name,
+        version: &new_release.version,
+        previous: previous,
+    };
+    ctx.out.print(&format!("Published {} {}", project.name, new_release.version), &created);
+
+    Ok(())
+}
+
+pub fn recompute_releases(ctx: &Context, matches: &ArgMatches) -> CommandResult {
+    let log = &ctx.log;
+    let project = ctx.project()?;
+    let checkouts = by_repository(ctx, &project, matches.values_of("filepath"))?;
+
+    if let Some(release_name) = matches.value_of("release") {
+        let release = find_release(ctx, &project, release_name)?;
+
+        let previous = matches.value_of("previous");
+        if let Some(previous) = previous {
+            find_release(ctx, &project, previous)?;
+        } else if !matches.is_present("no-previous") {
+            return Err(Error::Usage(String::from("Pass --previous or --no-previous along with --release")));
+        }
+
+        thanks::releases::set_previous(&ctx.conn, &release, previous)?;
+        match previous {
+            Some(previous) => info!(log, "{} now follows {}", release_name, previous),
+            None => info!(log, "{} now follows every earlier release", release_name),
+        }
+    }
+
+    let mut moved = 0;
+    for &(ref repository, ref path) in checkouts.iter() {
+        let mailmap = Mailmap::for_checkout(repository, path)?;
+        let reassignments: Vec<Reassignment> = thanks::releases::recompute(&ctx.conn, log, repository, path, &mailmap)?;
+
+        for reassignment in reassignments.iter() {
+            ctx.out.print(&format!("Moved {} from {} to {}", reassignment.sha, reassignment.from, reassignment.to),
+                          reassignment);
+        }
+
+        info!(log, "Moved {} commits in {}", reassignments.len(), repository.github_name);
+        moved += reassignments.len();
+    }
+
+    info!(log, "Moved {} commits", moved);
+
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct Visibility<'a> {
+    project: &'a str,
+    version: &'a str,
+    visible: bool,
+}
+
+pub fn visible(ctx: &Context, matches: &ArgMatches) -> CommandResult {
+    let project = ctx.project()?;
+    let release = find_release(ctx, &project, matches.value_of("version").unwrap())?;
+    let visible = !matches.is_present("hide");
+
+    thanks::releases::set_visible(&ctx.conn, &release, visible)?;
+
+    let visibility = Visibility {
+        project: &project.name,
+        version: &release.version,
+        visible: visible,
+    };
+    let text = if visible {
+        format!("Set version {} to show.", release.version)
+    } else {
+        format!("Set version {} to hide.", release.version)
+    };
+    ctx.out.print(&text, &visibility);
+
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct ReleaseNotes<'a> {
+    project: &'a str,
+    version: &'a str,
+    contributors: &'a [Contributor],
+}
+
+pub fn release_notes(ctx: &Context, matches: &ArgMatches) -> CommandResult {
+    let project = ctx.project()?;
+    let version = matches.value_of("version").unwrap();
+
+    let contributors = thanks::releases::contributor_list(&ctx.conn, &project, version, &DateRange::all())
+        .ok_or_else(|| Error::NotFound(format!("{} has no release {}", project.name, version)))?;
+
+    let section = match matches.value_of("format").unwrap() {
+        "html" => html(&project, version, &contributors),
+        "text" => text(&project, version, &contributors),
+        _ => markdown(&project, version, &contributors),
+    };
+
+    let notes = ReleaseNotes {
+        project: &project.name,
+        version: version,
+        contributors: &contributors,
+    };
+    ctx.out.print(section.trim_right(), &notes);
+
+    Ok(())
+}
+
+fn summary(project: &Project, version: &str, contributors: &[Contributor]) -> String {
+    let new = contributors.iter().filter(|c| c.first_time).count();
+
+    format!("{} individuals contributed to {} {}, {} of them for the first time. \
+             Thank you so much!",
+            contributors.len(), project.name, version, new)
+}
+
+fn markdown(project: &Project, version: &str, contributors: &[Contributor]) -> String {
+    // names are free-form, so anything markdown might interpret gets escaped
+    fn escape(s: &str) -> String {
+        let mut escaped = String::with_capacity(s.len());
+        for c in s.chars() {
+            if "\\`*_[]<>".contains(c) {
+                escaped.push('\\');
+            }
+            escaped.push(c);
+        }
+        escaped
+    }
+
+    let mut out = String::new();
+
+    out.push_str(&format!("### Contributors to {} {}\n\n", project.name, version));
+    out.push_str(&summary(project, version, contributors));
+    out.push_str("\n\n");
+
+    for contributor in contributors {
+        out.push_str(&format!("- {}\n", escape(&contributor.name)));
+    }
+
+    let new: Vec<_> = contributors.iter().filter(|c| c.first_time).collect();
+    if !new.is_empty() {
+        out.push_str("\n#### First-time contributors\n\n");
+        for contributor in new {
+            out.push_str(&format!("- {}\n", escape(&contributor.name)));
+        }
+    }
+
+    out
+}
+
+fn html(project: &Project, version: &str, contributors: &[Contributor]) -> String {
+    fn escape(s: &str) -> String {
+        s.replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+            .replace('"', "&quot;")
+    }
+
+    let mut out = String::new();
+
+    out.push_str(&format!("<h3>Contributors to {} {}</h3>\n",
+                          escape(&project.name), escape(version)));
+    out.push_str(&format!("<p>{}</p>\n", escape(&summary(project, version, contributors))));
+
+    out.push_str("<ul>\n");
+    for contributor in contributors {
+        out.push_str(&format!("  <li>{}</li>\n", escape(&contributor.name)));
+    }
+    out.push_str("</ul>\n");
+
+    let new: Vec<_> = contributors.iter().filter(|c| c.first_time).collect();
+    if !new.is_empty() {
+        out.push_str("<h4>First-time contributors</h4>\n<ul>\n");
+        for contributor in new {
+            out.push_str(&format!("  <li>{}</li>\n", escape(&contributor.name)));
+        }
+        out.push_str("</ul>\n");
+    }
+
+    out
+}
+
+fn text(project: &Project, version: &str, contributors: &[Contributor]) -> String {
+    let mut out = String::new();
+
+    out.push_str(&format!("Contributors to {} {}\n\n", project.name, version));
+    out.push_str(&summary(project, version, contributors));
+    out.push_str("\n\n");
+
+    for contributor in contributors {
+        out.push_str(&format!("{}\n", contributor.name));
+    }
+
+    let new: Vec<_> = contributors.iter().filter(|c| c.first_time).collect();
+    if !new.is_empty() {
+        out.push_str("\nFirst-time contributors:\n\n");
+        for contributor in new {
+            out.push_str(&format!("{}\n", contributor.name));
+        }
+    }
+
+    out
+}
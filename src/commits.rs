@@ -1,21 +1,221 @@
 use models::{Commit, NewCommit};
-use models::Author;
+use models::{Author, NewAuthor, NewCoAuthor};
 use models::Release;
 
+use git::{CoAuthor, LogEntry};
+
+use chrono::NaiveDateTime;
+
 use diesel;
 use diesel::pg::PgConnection;
 use diesel::prelude::*;
 
-pub fn create<'a>(conn: &PgConnection, sha: &'a str, author: &Author, release: &Release) -> Commit {
+use slog::Logger;
+
+use std::collections::{HashMap, HashSet};
+
+/// how many commits are saved at once when importing
+///
+/// each commit takes five parameters, and Postgres allows 65535 per query
+const IMPORT_CHUNK_SIZE: usize = 5000;
+
+/// how many commits are updated by a single query
+const UPDATE_CHUNK_SIZE: usize = 5000;
+
+pub fn create<'a>(conn: &PgConnection, sha: &'a str, author: &Author, release: &Release,
+                  authored_at: NaiveDateTime, committed_at: NaiveDateTime) -> QueryResult<Commit> {
     use schema::commits;
 
     let new_commit = NewCommit {
         sha: sha,
         release_id: release.id,
         author_id: author.id,
+        authored_at: Some(authored_at),
+        committed_at: Some(committed_at),
     };
 
     diesel::insert(&new_commit).into(commits::table)
         .get_result(conn)
-        .expect("Error saving new commit")
+}
+
+/// saves commits from a git log in `release`, along with their authors and
+/// co-authors, a chunk at a time
+///
+/// commits we already have are left alone. returns how many were added.
+pub fn import(conn: &PgConnection, log: &Logger, release: &Release, entries: &[LogEntry]) -> QueryResult<usize> {
+    let mut added = 0;
+
+    for (i, chunk) in entries.chunks(IMPORT_CHUNK_SIZE).enumerate() {
+        added += conn.transaction(|| import_chunk(conn, release, chunk))?;
+
+        info!(log, "Imported {} of {} commits", (i * IMPORT_CHUNK_SIZE + chunk.len()), entries.len());
+    }
+
+    Ok(added)
+}
+
+fn import_chunk(conn: &PgConnection, release: &Release, entries: &[LogEntry]) -> QueryResult<usize> {
+    use schema::commits;
+    use diesel::pg::upsert::*;
+
+    let mut new_authors: Vec<NewAuthor> = entries.iter()
+        .map(|entry| NewAuthor { name: &entry.author_name, email: &entry.author_email })
+        .collect();
+    new_authors.sort_by(|a, b| (a.email, a.name).cmp(&(b.email, b.name)));
+    new_authors.dedup_by(|a, b| a.email == b.email && a.name == b.name);
+
+    let author_ids = ::authors::find_or_create_all(conn, new_authors)?
+        .into_iter()
+        .map(|author| ((author.email, author.name), author.id))
+        .collect::<HashMap<_, _>>();
+
+    let new_commits: Vec<NewCommit> = entries.iter().map(|entry| {
+        NewCommit {
+            sha: &entry.sha,
+            release_id: release.id,
+            author_id: author_ids[&(entry.author_email.clone(), entry.author_name.clone())],
+            authored_at: Some(entry.authored_at),
+            committed_at: Some(entry.committed_at),
+        }
+    }).collect();
+
+    let added = diesel::insert(&new_commits.on_conflict_do_nothing())
+        .into(commits::table)
+        .execute(conn)?;
+
+    let co_authors = entries.iter()
+        .flat_map(|entry| entry.co_authors.iter().map(move |co_author| (entry.sha.as_str(), co_author)))
+        .collect();
+    add_co_authors(conn, co_authors)?;
+
+    Ok(added)
+}
+
+/// moves the commits in `entries`, which must already exist, to `release`, a
+/// chunk at a time
+///
+/// commits saved before we kept dates get theirs filled in along the way.
+/// returns how many commits were moved.
+pub fn reassign(conn: &PgConnection, release: &Release, entries: &[&LogEntry]) -> QueryResult<usize> {
+    let mut moved = 0;
+
+    for chunk in entries.chunks(UPDATE_CHUNK_SIZE) {
+        moved += conn.execute(&format!(
+            "UPDATE commits SET release_id = {}, \
+             authored_at = COALESCE(commits.authored_at, dates.authored_at), \
+             committed_at = COALESCE(commits.committed_at, dates.committed_at) \
+             FROM (VALUES {}) AS dates (sha, authored_at, committed_at) \
+             WHERE commits.sha = dates.sha",
+            release.id, dated_values(chunk)))?;
+    }
+
+    Ok(moved)
+}
+
+/// the shas and dates of `entries`, as the rows of a SQL `VALUES` list
+fn dated_values(entries: &[&LogEntry]) -> String {
+    let timestamp = |date: &NaiveDateTime| quote(&date.format("%Y-%m-%d %H:%M:%S").to_string());
+
+    entries.iter()
+        .map(|entry| {
+            format!("({}, TIMESTAMP {}, TIMESTAMP {})",
+                    quote(&entry.sha), timestamp(&entry.authored_at), timestamp(&entry.committed_at))
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// which of `shas` we already have
+pub fn existing(conn: &PgConnection, shas: Vec<&str>) -> QueryResult<HashSet<String>> {
+    use schema::commits::dsl::*;
+    use diesel::expression::dsl::any;
+
+    commits.filter(sha.eq(any(shas)))
+        .select(sha)
+        .load(conn)
+        .map(|existing: Vec<String>| existing.into_iter().collect())
+}
+
+/// credits the people named in `Co-authored-by:` trailers with commits
+///
+/// takes (sha, co-author) pairs; the commits must already exist. co-authors
+/// who were already recorded are skipped, so this is safe to repeat.
+pub fn add_co_authors(conn: &PgConnection, co_authors: Vec<(&str, &CoAuthor)>) -> QueryResult<usize> {
+    use schema::co_authors;
+    use diesel::pg::upsert::*;
+
+    if co_authors.is_empty() {
+        // https://github.com/diesel-rs/diesel/issues/797
+        return Ok(0);
+    }
+
+    let new_authors = co_authors.iter().map(|&(_, co_author)| {
+        NewAuthor { name: &co_author.name, email: &co_author.email }
+    }).collect();
+    let author_ids = ::authors::find_or_create_all(conn, new_authors)?
+        .into_iter()
+        .map(|author| ((author.email, author.name), author.id))
+        .collect::<HashMap<_, _>>();
+
+    let new_co_authors: Vec<NewCoAuthor> = co_authors.iter().map(|&(sha, co_author)| {
+        NewCoAuthor {
+            sha: sha,
+            author_id: author_ids[&(co_author.email.clone(), co_author.name.clone())],
+        }
+    }).collect();
+
+    diesel::insert(&new_co_authors.on_conflict_do_nothing())
+        .into(co_authors::table)
+        .execute(conn)
+}
+
+/// records which of the commits in `shas` were cherry-picked from which, so
+/// that they're only counted once on the leaderboards
+///
+/// takes (backport, original) pairs. originals we don't have are skipped,
+/// since then the backport is the only copy we'll count. the rest of `shas`
+/// are no longer marked as backports, so that a wrong match can be undone.
+pub fn mark_backports(conn: &PgConnection, shas: Vec<&str>, backports: Vec<(String, String)>) -> QueryResult<()> {
+    use schema::commits::dsl::*;
+    use diesel::expression::dsl::any;
+
+    let originals: Vec<&str> = backports.iter().map(|&(_, ref original)| original.as_str()).collect();
+    let known: HashSet<String> = commits.filter(sha.eq(any(originals)))
+        .select(sha)
+        .load::<String>(conn)?
+        .into_iter()
+        .collect();
+    let backports: Vec<&(String, String)> = backports.iter()
+        .filter(|&&(_, ref original)| known.contains(original))
+        .collect();
+
+    conn.transaction(|| {
+        diesel::update(commits.filter(sha.eq(any(shas))))
+            .set(backport_of.eq(None::<String>))
+            .execute(conn)?;
+
+        for chunk in backports.chunks(UPDATE_CHUNK_SIZE) {
+            let values = chunk.iter()
+                .map(|&&(ref backport, ref original)| format!("({}, {})", quote(backport), quote(original)))
+                .collect::<Vec<_>>()
+                .join(", ");
+
+            conn.execute(&format!(
+                "UPDATE commits SET backport_of = backports.original \
+                 FROM (VALUES {}) AS backports (sha, original) \
+                 WHERE commits.sha = backports.sha",
+                values))?;
+        }
+
+        Ok(())
+    })
+}
+
+/// quotes `text` as a SQL string
+///
+/// diesel can't update from a list of values, so those queries are built by
+/// hand. everything that goes in them comes from git or is formatted by us,
+/// but it's escaped anyway.
+fn quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "''"))
 }
@@ -0,0 +1,126 @@
+//! The file describing every project.
+//!
+//! `sync` makes the database match it. It looks like this:
+//!
+//! ```toml
+//! [[project]]
+//! name = "Rust"
+//! url_path = "rust"
+//! bots = ["bors@rust-lang.org"]
+//!
+//! [[project.repository]]
+//! github_name = "rust-lang/rust"
+//!
+//! [[project.repository]]
+//! github_name = "rust-lang/cargo"
+//! tags = { "1.19.0" = "0.20.0" }
+//!
+//! [project.releases."1.12.1"]
+//! previous = "1.12.0"
+//! ```
+
+use toml;
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::Read;
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    #[serde(default, rename = "project")]
+    pub projects: Vec<ProjectConfig>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProjectConfig {
+    pub name: String,
+    pub url_path: String,
+    /// the repository the project is named after, if it isn't the first one
+    pub github_name: Option<String>,
+    /// emails of authors whose commits aren't credited
+    #[serde(default)]
+    pub bots: Vec<String>,
+    #[serde(default, rename = "repository")]
+    pub repositories: Vec<RepositoryConfig>,
+    /// settings of single releases, by version
+    #[serde(default)]
+    pub releases: BTreeMap<String, ReleaseConfig>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RepositoryConfig {
+    pub github_name: String,
+    #[serde(default = "default_tag_pattern")]
+    pub tag_pattern: String,
+    #[serde(default = "default_branch")]
+    pub default_branch: String,
+    pub mailmap: Option<String>,
+    /// tags of releases that don't follow the tag pattern, by version
+    #[serde(default)]
+    pub tags: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ReleaseConfig {
+    /// the release this one follows, if it doesn't follow every earlier one
+    pub previous: Option<String>,
+    /// whether the release is shown; left alone if it isn't given
+    pub visible: Option<bool>,
+}
+
+fn default_tag_pattern() -> String {
+    String::from("{version}")
+}
+
+fn default_branch() -> String {
+    String::from("master")
+}
+
+impl Config {
+    /// reads and checks the config at `path`
+    pub fn load(path: &str) -> Result<Config, String> {
+        let mut contents = String::new();
+        File::open(path)
+            .and_then(|mut file| file.read_to_string(&mut contents))
+            .map_err(|e| format!("Error reading {}: {}", path, e))?;
+
+        let config: Config = toml::from_str(&contents)
+            .map_err(|e| format!("Error parsing {}: {}", path, e))?;
+        config.check()?;
+
+        Ok(config)
+    }
+
+    fn check(&self) -> Result<(), String> {
+        for (i, project) in self.projects.iter().enumerate() {
+            if self.projects[..i].iter().any(|p| p.name == project.name) {
+                return Err(format!("{} is in the config twice", project.name));
+            }
+            if project.repositories.is_empty() {
+                return Err(format!("{} needs at least one repository", project.name));
+            }
+
+            for repository in project.repositories.iter() {
+                if !repository.tag_pattern.contains("{version}") {
+                    return Err(format!("The tag pattern of {} must contain {{version}}", repository.github_name));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl ProjectConfig {
+    /// the repository the project is named after
+    pub fn github_name(&self) -> &str {
+        match self.github_name {
+            Some(ref github_name) => github_name,
+            None => &self.repositories[0].github_name,
+        }
+    }
+}
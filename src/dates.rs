@@ -0,0 +1,92 @@
+//! Windows of time to restrict the leaderboards to.
+
+use chrono::{Duration, NaiveDate};
+
+/// the days from `since` to `until`, inclusive
+///
+/// either end may be left open
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DateRange {
+    pub since: Option<NaiveDate>,
+    pub until: Option<NaiveDate>,
+}
+
+impl DateRange {
+    /// all of time, which also includes commits we don't have dates for
+    pub fn all() -> DateRange {
+        DateRange::default()
+    }
+
+    /// parses `<since>..<until>`, with dates written as `YYYY-MM-DD`
+    ///
+    /// either date can be left out, so `2016-01-01..` means "since 2016".
+    /// ranges that end before they start aren't valid.
+    pub fn parse(range: &str) -> Option<DateRange> {
+        let mut parts = range.splitn(2, "..");
+
+        let since = match parse_date(parts.next().unwrap()) {
+            Some(since) => since,
+            None => return None,
+        };
+        let until = match parts.next().map(parse_date) {
+            Some(Some(until)) => until,
+            _ => return None,
+        };
+
+        if let (Some(since), Some(until)) = (since, until) {
+            if since > until {
+                return None;
+            }
+        }
+
+        Some(DateRange {
+            since: since,
+            until: until,
+        })
+    }
+
+    pub fn is_all(&self) -> bool {
+        self.since.is_none() && self.until.is_none()
+    }
+
+    /// a SQL condition on `contributions.authored_at` that matches this range
+    ///
+    /// the dates are formatted by us, so they're safe to put in the query
+    pub fn sql_filter(&self) -> String {
+        if self.is_all() {
+            return String::from("TRUE");
+        }
+
+        let mut conditions = vec![String::from("contributions.authored_at IS NOT NULL")];
+
+        if let Some(since) = self.since {
+            conditions.push(format!("contributions.authored_at >= '{}'", since.format("%Y-%m-%d")));
+        }
+
+        if let Some(until) = self.until {
+            let end = until + Duration::days(1);
+            conditions.push(format!("contributions.authored_at < '{}'", end.format("%Y-%m-%d")));
+        }
+
+        conditions.join(" AND ")
+    }
+
+    /// describes the range for people, like "between 2016-01-01 and 2016-12-31"
+    pub fn description(&self) -> String {
+        match (self.since, self.until) {
+            (Some(since), Some(until)) => format!("between {} and {}", since, until),
+            (Some(since), None) => format!("since {}", since),
+            (None, Some(until)) => format!("until {}", until),
+            (None, None) => String::from("of all time"),
+        }
+    }
+}
+
+/// parses one end of a range, where an empty string means it's open
+fn parse_date(date: &str) -> Option<Option<NaiveDate>> {
+    if date.is_empty() {
+        return Some(None);
+    }
+
+    NaiveDate::parse_from_str(date, "%Y-%m-%d").ok().map(Some)
+}
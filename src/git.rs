@@ -0,0 +1,330 @@
+//! Reading commits out of a local checkout.
+
+use mailmap::Mailmap;
+
+use chrono::NaiveDateTime;
+
+use std::io::prelude::*;
+use std::process::{Command, Stdio};
+use std::str;
+use std::thread;
+
+/// a commit, as reported by `git log`
+#[derive(Debug)]
+pub struct LogEntry {
+    pub sha: String,
+    pub author_email: String,
+    pub author_name: String,
+    pub authored_at: NaiveDateTime,
+    pub committed_at: NaiveDateTime,
+    /// everyone credited by a `Co-authored-by:` trailer
+    pub co_authors: Vec<CoAuthor>,
+    /// the sha in a `(cherry picked from commit ...)` line, as added by
+    /// `git cherry-pick -x`
+    pub cherry_picked_from: Option<String>,
+}
+
+/// someone named in a `Co-authored-by:` trailer
+#[derive(Debug, Clone, PartialEq)]
+pub struct CoAuthor {
+    pub name: String,
+    pub email: String,
+}
+
+/// runs `git log` over `revisions` in the repository at `path`
+///
+/// `revisions` are passed on as they are, so they can be anything `git log`
+/// understands, like `["1.13.0", "--not", "1.12.0"]`. authors and co-authors
+/// are resolved through `mailmap`.
+pub fn log(path: &str, revisions: &[&str], mailmap: &Mailmap) -> Vec<LogEntry> {
+    // every entry starts with a record separator, since messages can
+    // contain anything else
+    let mut args = vec!["log", "--format=%x1e%H %at %ct %ae %an%n%B"];
+    args.extend_from_slice(revisions);
+
+    git(path, &args)
+        .split('\x1e')
+        .filter(|s| !s.is_empty())
+        .map(|entry| parse_entry(entry, mailmap))
+        .collect()
+}
+
+/// every tag in the repository at `path`
+pub fn tags(path: &str) -> Vec<String> {
+    git(path, &["tag", "--list"])
+        .lines()
+        .filter(|s| !s.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+/// the shas of every commit in `revisions`, which are passed on to
+/// `git rev-list` as they are
+pub fn rev_list(path: &str, revisions: &[&str]) -> Vec<String> {
+    let mut args = vec!["rev-list"];
+    args.extend_from_slice(revisions);
+
+    git(path, &args)
+        .lines()
+        .filter(|s| !s.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+/// the patch id of every commit in `revisions`, as (sha, patch id) pairs
+///
+/// commits with the same patch id make the same change, so a commit that was
+/// cherry-picked onto another branch has the same patch id as the original.
+/// merges don't have one.
+pub fn patch_ids(path: &str, revisions: &[&str]) -> Vec<(String, String)> {
+    let mut args = vec!["log", "-p", "--no-color", "--no-merges"];
+    args.extend_from_slice(revisions);
+    let patches = git(path, &args);
+
+    let mut patch_id = Command::new("git")
+        .arg("-C")
+        .arg(path)
+        .arg("patch-id")
+        .arg("--stable")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("failed to execute process");
+
+    // patch-id writes while it reads, so feed it from another thread to keep
+    // both pipes from filling up
+    let mut stdin = patch_id.stdin.take().unwrap();
+    let writer = thread::spawn(move || stdin.write_all(patches.as_bytes()));
+
+    let output = patch_id.wait_with_output().expect("failed to execute process");
+    writer.join().unwrap().expect("Error writing to git patch-id");
+
+    if !output.status.success() {
+        panic!("git patch-id failed:\n\nstderr:\n{}", str::from_utf8(&output.stderr).unwrap());
+    }
+
+    str::from_utf8(&output.stdout).unwrap()
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.split(' ');
+
+            match (parts.next(), parts.next()) {
+                (Some(id), Some(sha)) => Some((sha.to_string(), id.to_string())),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// runs a git command in the repository at `path` and returns its output
+fn git(path: &str, args: &[&str]) -> String {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(path)
+        .arg("--no-pager")
+        .args(args)
+        .output()
+        .expect("failed to execute process");
+
+    if !output.status.success() {
+        let stdout = str::from_utf8(&output.stdout).unwrap();
+        let stderr = str::from_utf8(&output.stderr).unwrap();
+        panic!(
+            "git {} failed:\n\nstdout:\n{}\n\nstderr:\n{}",
+            args[0],
+            stdout,
+            stderr,
+        );
+    }
+
+    String::from_utf8(output.stdout).unwrap()
+}
+
+fn parse_entry(entry: &str, mailmap: &Mailmap) -> LogEntry {
+    let mut lines = entry.splitn(2, '\n');
+    let line = lines.next().unwrap();
+    let message = lines.next().unwrap_or("");
+
+    let mut parts = line.splitn(5, ' ');
+
+    let sha = parts.next().unwrap();
+    let authored_at = parts.next().unwrap();
+    let committed_at = parts.next().unwrap();
+    let author_email = parts.next().unwrap();
+    let author_name = parts.next().unwrap();
+
+    let (author_name, author_email) = mailmap.resolve(author_name, author_email);
+
+    LogEntry {
+        sha: sha.to_string(),
+        author_email: author_email,
+        author_name: author_name,
+        authored_at: timestamp(authored_at),
+        committed_at: timestamp(committed_at),
+        co_authors: co_authors(message, &author_email, mailmap),
+        cherry_picked_from: cherry_picked_from(message),
+    }
+}
+
+/// finds the `(cherry picked from commit <sha>)` line in a commit message
+pub fn cherry_picked_from(message: &str) -> Option<String> {
+    message.lines()
+        .filter_map(|line| {
+            let line = line.trim();
+
+            if line.starts_with("(cherry picked from commit ") && line.ends_with(')') {
+                Some(line["(cherry picked from commit ".len()..line.len() - 1].trim().to_string())
+            } else {
+                None
+            }
+        })
+        .last()
+}
+
+/// finds the `Co-authored-by: Name <email>` trailers in a commit message
+///
+/// like git, only the last paragraph of the message is read, so lines quoted
+/// in the middle of it aren't taken for trailers. the key is matched
+/// case-insensitively, as GitHub does, and trailers that aren't in that form
+/// are ignored. co-authors are resolved through `mailmap`, and the commit's
+/// own author, whose resolved email is `author_email`, isn't counted twice.
+pub fn co_authors(message: &str, author_email: &str, mailmap: &Mailmap) -> Vec<CoAuthor> {
+    let author_email = author_email.to_lowercase();
+    let mut co_authors: Vec<CoAuthor> = Vec::new();
+
+    for line in trailer_lines(message) {
+        let mut parts = line.trim().splitn(2, ':');
+
+        let key = parts.next().unwrap();
+        let value = match parts.next() {
+            Some(value) if key.trim().to_lowercase() == "co-authored-by" => value.trim(),
+            _ => continue,
+        };
+
+        let (start, end) = match (value.find('<'), value.rfind('>')) {
+            (Some(start), Some(end)) if start < end => (start, end),
+            _ => continue,
+        };
+
+        let (name, email) = mailmap.resolve(value[..start].trim(), value[start + 1..end].trim());
+        let co_author = CoAuthor {
+            name: name,
+            email: email,
+        };
+
+        if co_author.name.is_empty() || co_author.email.is_empty() || co_author.email.to_lowercase() == author_email {
+            continue;
+        }
+
+        if !co_authors.contains(&co_author) {
+            co_authors.push(co_author);
+        }
+    }
+
+    co_authors
+}
+
+/// the lines of the last paragraph of a commit message, which is where git
+/// looks for trailers
+///
+/// a message that's only a subject has none
+fn trailer_lines(message: &str) -> Vec<&str> {
+    let mut paragraphs: Vec<Vec<&str>> = Vec::new();
+    let mut paragraph: Vec<&str> = Vec::new();
+
+    for line in message.lines() {
+        if !line.trim().is_empty() {
+            paragraph.push(line);
+        } else if !paragraph.is_empty() {
+            paragraphs.push(paragraph);
+            paragraph = Vec::new();
+        }
+    }
+    if !paragraph.is_empty() {
+        paragraphs.push(paragraph);
+    }
+
+    if paragraphs.len() < 2 {
+        return Vec::new();
+    }
+
+    paragraphs.pop().unwrap_or_default()
+}
+
+/// git prints dates as seconds since the epoch, in UTC
+fn timestamp(seconds: &str) -> NaiveDateTime {
+    let seconds = seconds.parse().expect(&format!("invalid timestamp {}", seconds));
+
+    NaiveDateTime::from_timestamp(seconds, 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{co_authors, CoAuthor};
+
+    use mailmap::Mailmap;
+
+    fn co_author(name: &str, email: &str) -> CoAuthor {
+        CoAuthor {
+            name: name.to_string(),
+            email: email.to_string(),
+        }
+    }
+
+    #[test]
+    fn finds_co_authors_in_the_last_paragraph() {
+        let message = "Add a page for every author\n\
+                       \n\
+                       They link to each other.\n\
+                       \n\
+                       Co-authored-by: Jane Doe <jane@example.com>\n\
+                       co-authored-by: John Doe <john@example.com>\n\
+                       Signed-off-by: Jane Doe <jane@example.com>\n";
+
+        assert_eq!(co_authors(message, "someone@example.com", &Mailmap::empty()),
+                   vec![co_author("Jane Doe", "jane@example.com"), co_author("John Doe", "john@example.com")]);
+    }
+
+    #[test]
+    fn ignores_trailers_before_the_last_paragraph() {
+        let message = "Revert \"Add a page for every author\"\n\
+                       \n\
+                       This reverts a commit that said:\n\
+                       \n\
+                       Co-authored-by: Jane Doe <jane@example.com>\n\
+                       \n\
+                       Co-authored-by: John Doe <john@example.com>";
+
+        assert_eq!(co_authors(message, "someone@example.com", &Mailmap::empty()),
+                   vec![co_author("John Doe", "john@example.com")]);
+    }
+
+    #[test]
+    fn ignores_a_subject_that_looks_like_a_trailer() {
+        let message = "Co-authored-by: Jane Doe <jane@example.com>";
+
+        assert_eq!(co_authors(message, "someone@example.com", &Mailmap::empty()), vec![]);
+    }
+
+    #[test]
+    fn leaves_out_the_author() {
+        let message = "Add a page for every author\n\
+                       \n\
+                       Co-authored-by: Jane Doe <Jane@Example.com>\n\
+                       Co-authored-by: John Doe <john@example.com>";
+
+        assert_eq!(co_authors(message, "jane@example.com", &Mailmap::empty()),
+                   vec![co_author("John Doe", "john@example.com")]);
+    }
+
+    #[test]
+    fn leaves_out_the_author_under_another_email() {
+        let message = "Add a page for every author\n\
+                       \n\
+                       Co-authored-by: jdoe <jane@laptop.local>";
+        let mailmap = Mailmap::parse("Jane Doe <jane@example.com> <jane@laptop.local>");
+
+        assert_eq!(co_authors(message, "jane@example.com", &mailmap), vec![]);
+    }
+}
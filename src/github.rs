@@ -0,0 +1,516 @@
+//! Fetching new commits from the GitHub API.
+//!
+//! This is how commits get in between releases, without a checkout of the
+//! project. `GITHUB_API_URL` changes where the API is (`https://api.github.com`
+//! by default), and `GITHUB_TOKEN` is sent along if it's set, which raises the
+//! rate limit a lot.
+
+use mailmap::Mailmap;
+use models::{GitHubSync, Project, Release, Repository};
+
+use chrono::{DateTime, Duration, NaiveDateTime, UTC};
+
+use diesel;
+use diesel::pg::PgConnection;
+use diesel::prelude::*;
+
+use reqwest;
+use reqwest::header::Headers;
+
+use slog::Logger;
+
+use std::env;
+use std::error::Error;
+use std::thread;
+use std::time;
+
+/// how many times a request is tried before giving up
+const MAX_ATTEMPTS: u32 = 5;
+
+/// the longest we'll wait for the rate limit to reset
+const MAX_WAIT_SECONDS: u64 = 60 * 60;
+
+/// how far before the last sync to start the next one, for commits that
+/// took a while to be pushed
+const OVERLAP_DAYS: i64 = 1;
+
+/// a commit, as the commits API describes it
+#[derive(Debug, Deserialize)]
+pub struct Commit {
+    pub sha: String,
+    pub commit: CommitDetails,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CommitDetails {
+    pub author: Signature,
+    pub committer: Signature,
+    pub message: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Signature {
+    pub name: String,
+    pub email: String,
+    pub date: String,
+}
+
+impl Commit {
+    pub fn to_pushed(&self) -> Result<PushedCommit, Box<Error>> {
+        Ok(PushedCommit {
+            sha: &self.sha,
+            author_name: &self.commit.author.name,
+            author_email: &self.commit.author.email,
+            authored_at: parse_date(&self.commit.author.date)?,
+            committed_at: parse_date(&self.commit.committer.date)?,
+            message: &self.commit.message,
+        })
+    }
+}
+
+/// a commit that was pushed to GitHub, whichever API told us about it
+#[derive(Debug)]
+pub struct PushedCommit<'a> {
+    pub sha: &'a str,
+    pub author_name: &'a str,
+    pub author_email: &'a str,
+    pub authored_at: NaiveDateTime,
+    pub committed_at: NaiveDateTime,
+    pub message: &'a str,
+}
+
+pub struct Client {
+    client: reqwest::Client,
+    base_url: String,
+    token: Option<String>,
+    log: Logger,
+}
+
+impl Client {
+    /// a client for the API at `base_url`, authenticated with `token` if
+    /// there is one
+    pub fn new(log: &Logger, base_url: &str, token: Option<String>) -> Result<Client, Box<Error>> {
+        Ok(Client {
+            client: reqwest::Client::new()?,
+            base_url: base_url.trim_right_matches('/').to_string(),
+            token: token,
+            log: log.clone(),
+        })
+    }
+
+    /// a client set up from `GITHUB_API_URL` and `GITHUB_TOKEN`
+    pub fn from_env(log: &Logger) -> Result<Client, Box<Error>> {
+        let base_url = env::var("GITHUB_API_URL")
+            .unwrap_or_else(|_| String::from("https://api.github.com"));
+
+        Client::new(log, &base_url, env::var("GITHUB_TOKEN").ok())
+    }
+
+    /// every commit to a branch of a repository since `since`, newest first,
+    /// along with the ETag of the first page
+    ///
+    /// returns `None` if the first page still has the ETag `etag`, which
+    /// means nothing changed
+    pub fn commits(&self, repository: &str, branch: &str, since: &NaiveDateTime, etag: Option<&str>)
+        -> Result<Option<(Vec<Commit>, Option<String>)>, Box<Error>>
+    {
+        let mut url = Some(format!("{}/repos/{}/commits?sha={}&since={}&per_page=100",
+                                   self.base_url, repository, branch, since.format("%Y-%m-%dT%H:%M:%SZ")));
+        let mut commits = Vec::new();
+        let mut first_etag = None;
+        let mut first_page = true;
+
+        while let Some(page_url) = url {
+            debug!(self.log, "Fetching {}", page_url);
+
+            let mut resp = self.get(&page_url, if first_page { etag } else { None })?;
+            let status = resp.status().to_u16();
+
+            if first_page && status == 304 {
+                return Ok(None);
+            }
+            if status != 200 {
+                return Err(format!("GitHub responded to {} with {}", page_url, resp.status()).into());
+            }
+
+            if first_page {
+                first_etag = header(resp.headers(), "ETag");
+                first_page = false;
+            }
+
+            url = header(resp.headers(), "Link").and_then(|link| next_page(&link));
+
+            let page: Vec<Commit> = resp.json()?;
+            commits.extend(page);
+        }
+
+        Ok(Some((commits, first_etag)))
+    }
+
+    /// sends a GET request, waiting out rate limits and retrying server errors
+    fn get(&self, url: &str, etag: Option<&str>) -> Result<reqwest::Response, Box<Error>> {
+        for attempt in 0..MAX_ATTEMPTS {
+            let mut headers = Headers::new();
+            headers.set_raw("Accept", vec![b"application/vnd.github.v3+json".to_vec()]);
+            headers.set_raw("User-Agent", vec![b"thanks".to_vec()]);
+            if let Some(ref token) = self.token {
+                headers.set_raw("Authorization", vec![format!("token {}", token).into_bytes()]);
+            }
+            if let Some(etag) = etag {
+                headers.set_raw("If-None-Match", vec![etag.as_bytes().to_vec()]);
+            }
+
+            let resp = self.client.get(url).headers(headers).send()?;
+            let status = resp.status().to_u16();
+
+            if let Some(wait) = rate_limit_wait(status, resp.headers(), UTC::now().timestamp()) {
+                if wait > MAX_WAIT_SECONDS {
+                    return Err(format!("Rate limited by GitHub for another {} seconds", wait).into());
+                }
+
+                warn!(self.log, "Rate limited by GitHub, waiting {} seconds", wait);
+                thread::sleep(time::Duration::from_secs(wait));
+                continue;
+            }
+
+            if status >= 500 {
+                let wait = 2u64.pow(attempt);
+                warn!(self.log, "GitHub responded with {}, retrying in {} seconds", resp.status(), wait);
+                thread::sleep(time::Duration::from_secs(wait));
+                continue;
+            }
+
+            return Ok(resp);
+        }
+
+        Err(format!("Giving up on {} after {} attempts", url, MAX_ATTEMPTS).into())
+    }
+}
+
+/// how many seconds to wait before trying again, if a response with `status`
+/// and `headers` at the unix time `now` says we hit the rate limit
+fn rate_limit_wait(status: u16, headers: &Headers, now: i64) -> Option<u64> {
+    if status != 403 && status != 429 {
+        return None;
+    }
+
+    // the abuse limits say how long to wait
+    if let Some(retry_after) = header(headers, "Retry-After").and_then(|s| s.parse().ok()) {
+        return Some(retry_after);
+    }
+
+    // the normal limit says when it resets
+    if header(headers, "X-RateLimit-Remaining").as_ref().map(String::as_str) == Some("0") {
+        let reset: i64 = header(headers, "X-RateLimit-Reset")
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(0);
+
+        return Some(if reset > now { (reset - now) as u64 + 1 } else { 1 });
+    }
+
+    None
+}
+
+/// the first value of a response header
+fn header(headers: &Headers, name: &str) -> Option<String> {
+    headers.get_raw(name)
+        .and_then(|values| values.first())
+        .map(|value| String::from_utf8_lossy(value).into_owned())
+}
+
+/// finds the url of the next page in a `Link` header, like
+/// `<https://api.github.com/...&page=2>; rel="next", <...>; rel="last"`
+fn next_page(link: &str) -> Option<String> {
+    link.split(',')
+        .find(|part| part.split(';').skip(1).any(|param| param.trim() == r#"rel="next""#))
+        .and_then(|part| {
+            let url = part.split(';').next().unwrap().trim();
+
+            if url.starts_with('<') && url.ends_with('>') {
+                Some(url[1..url.len() - 1].to_string())
+            } else {
+                None
+            }
+        })
+}
+
+/// GitHub sends ISO 8601 dates; we store them in UTC
+pub fn parse_date(date: &str) -> Result<NaiveDateTime, Box<Error>> {
+    DateTime::parse_from_rfc3339(date)
+        .map(|date| date.naive_utc())
+        .map_err(|e| format!("invalid date {}: {}", date, e).into())
+}
+
+/// fetches one of a project's repositories' new commits from GitHub and adds
+/// them to the project's master
+///
+/// returns how many commits were added
+pub fn sync(conn: &PgConnection, log: &Logger, client: &Client, project: &Project, repository: &Repository)
+    -> Result<usize, Box<Error>>
+{
+    // the API reports authors as they wrote them, so apply the mailmap
+    // ourselves, like git does for the other commands
+    let mailmap = Mailmap::for_github(repository)?;
+    let branch = repository.default_branch.as_str();
+    let github_name = repository.github_name.as_str();
+
+    // pick up from the last sync that went through, however long ago that
+    // was. asking from the start of the day before it keeps the request the
+    // same all day, so that the ETag can tell us nothing changed, and leaves
+    // plenty of overlap for commits that took a while to be pushed
+    let started = UTC::now().naive_utc();
+    let last_sync = last_sync(conn, github_name)?;
+    let synced_at = last_sync.as_ref().map_or(started, |sync| sync.synced_at);
+    let since = (synced_at - Duration::days(OVERLAP_DAYS)).date().and_hms(0, 0, 0);
+    let etag = last_sync.and_then(|sync| if sync.since == since { sync.etag } else { None });
+
+    let (commits, etag) = match client.commits(github_name, branch, &since, etag.as_ref().map(String::as_str))? {
+        Some(fetched) => fetched,
+        None => {
+            info!(log, "Nothing new in {}", github_name);
+            save_sync(conn, GitHubSync {
+                repository: github_name.to_string(),
+                since: since,
+                etag: etag.clone(),
+                synced_at: started,
+            })?;
+            ::repositories::finish_sync_requests(conn, repository, started)?;
+            return Ok(0);
+        }
+    };
+    info!(log, "Found {} commits in {} since {}", commits.len(), github_name, since);
+
+    let master = ::releases::master(conn, project)?;
+
+    let mut added = 0;
+    for commit in commits.iter() {
+        if record(conn, log, &master, &mailmap, &commit.to_pushed()?)? {
+            added += 1;
+        }
+    }
+
+    // only now that every commit is in is it safe to start from here next
+    // time
+    save_sync(conn, GitHubSync {
+        repository: github_name.to_string(),
+        since: since,
+        etag: etag,
+        synced_at: started,
+    })?;
+    ::repositories::finish_sync_requests(conn, repository, started)?;
+
+    Ok(added)
+}
+
+/// adds a commit to `master` along with its author and co-authors, unless we
+/// already have it
+///
+/// the commit and its co-authors are saved together, so that a commit we
+/// have is never missing any. returns whether it was added.
+pub fn record(conn: &PgConnection, log: &Logger, master: &Release, mailmap: &Mailmap, commit: &PushedCommit)
+    -> Result<bool, Box<Error>>
+{
+    use schema::commits;
+
+    let sha = commit.sha;
+
+    conn.transaction::<_, Box<Error>, _>(|| {
+        let existing = commits::table.find(sha)
+            .select(commits::sha)
+            .first::<String>(conn)
+            .optional()?;
+        if existing.is_some() {
+            debug!(log, "Commit {} already in db, skipping", sha);
+            return Ok(false);
+        }
+
+        info!(log, "Creating commit {} for release {}", sha, master.version);
+
+        let (author_name, author_email) = mailmap.resolve(commit.author_name, commit.author_email);
+        let author = ::authors::load_or_create(conn, &author_name, &author_email)?;
+        ::commits::create(conn, sha, &author, master, commit.authored_at, commit.committed_at)?;
+
+        let co_authors = ::git::co_authors(commit.message, &author_email, mailmap);
+        ::commits::add_co_authors(conn, co_authors.iter().map(|co_author| (sha, co_author)).collect())?;
+
+        Ok(true)
+    })
+}
+
+fn last_sync(conn: &PgConnection, repository: &str) -> QueryResult<Option<GitHubSync>> {
+    use schema::github_syncs;
+
+    github_syncs::table.find(repository)
+        .first(conn)
+        .optional()
+}
+
+fn save_sync(conn: &PgConnection, sync: GitHubSync) -> QueryResult<()> {
+    use schema::github_syncs;
+
+    conn.transaction(|| {
+        diesel::delete(github_syncs::table.find(&sync.repository))
+            .execute(conn)?;
+        diesel::insert(&sync).into(github_syncs::table)
+            .execute(conn)?;
+
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{next_page, rate_limit_wait, Client};
+
+    use chrono::NaiveDate;
+
+    use reqwest::header::Headers;
+
+    use slog::{Discard, Logger};
+
+    use std::io::prelude::*;
+    use std::net::{TcpListener, TcpStream};
+    use std::thread;
+
+    fn headers(values: &[(&str, &str)]) -> Headers {
+        let mut headers = Headers::new();
+        for &(name, value) in values {
+            headers.set_raw(name.to_string(), vec![value.as_bytes().to_vec()]);
+        }
+
+        headers
+    }
+
+    #[test]
+    fn finds_the_next_page() {
+        let link = r#"<https://api.github.com/repositories/1/commits?page=2>; rel="next", <https://api.github.com/repositories/1/commits?page=5>; rel="last""#;
+
+        assert_eq!(next_page(link), Some(String::from("https://api.github.com/repositories/1/commits?page=2")));
+    }
+
+    #[test]
+    fn finds_the_next_page_anywhere_in_the_header() {
+        let link = r#"<https://api.github.com/x?page=1>; rel="prev", <https://api.github.com/x?page=3>; rel="next""#;
+
+        assert_eq!(next_page(link), Some(String::from("https://api.github.com/x?page=3")));
+    }
+
+    #[test]
+    fn has_no_next_page_on_the_last_page() {
+        let link = r#"<https://api.github.com/x?page=1>; rel="first", <https://api.github.com/x?page=4>; rel="prev""#;
+
+        assert_eq!(next_page(link), None);
+        assert_eq!(next_page(""), None);
+    }
+
+    #[test]
+    fn waits_as_long_as_retry_after_says() {
+        assert_eq!(rate_limit_wait(403, &headers(&[("Retry-After", "30")]), 1000), Some(30));
+        assert_eq!(rate_limit_wait(429, &headers(&[("Retry-After", "5")]), 1000), Some(5));
+    }
+
+    #[test]
+    fn waits_until_the_rate_limit_resets() {
+        let limited = headers(&[("X-RateLimit-Remaining", "0"), ("X-RateLimit-Reset", "1010")]);
+
+        assert_eq!(rate_limit_wait(403, &limited, 1000), Some(11));
+        assert_eq!(rate_limit_wait(403, &limited, 2000), Some(1));
+    }
+
+    #[test]
+    fn does_not_wait_without_a_rate_limit() {
+        let remaining = headers(&[("X-RateLimit-Remaining", "10"), ("X-RateLimit-Reset", "1010")]);
+
+        assert_eq!(rate_limit_wait(200, &headers(&[("Retry-After", "30")]), 1000), None);
+        assert_eq!(rate_limit_wait(403, &remaining, 1000), None);
+        assert_eq!(rate_limit_wait(403, &headers(&[]), 1000), None);
+    }
+
+    /// a stub of the API that answers one request per connection with each
+    /// of `responses` in turn, and hands back the requests it got
+    fn serve(listener: TcpListener, responses: Vec<String>) -> thread::JoinHandle<Vec<String>> {
+        thread::spawn(move || {
+            responses.into_iter().map(|response| {
+                let (mut stream, _) = listener.accept().unwrap();
+                let request = read_request(&mut stream);
+                stream.write_all(response.as_bytes()).unwrap();
+
+                request
+            }).collect()
+        })
+    }
+
+    fn read_request(stream: &mut TcpStream) -> String {
+        let mut request = Vec::new();
+        let mut byte = [0; 1];
+
+        while !request.ends_with(b"\r\n\r\n") {
+            stream.read_exact(&mut byte).unwrap();
+            request.push(byte[0]);
+        }
+
+        String::from_utf8(request).unwrap()
+    }
+
+    fn response(status: &str, headers: &[(&str, &str)], body: &str) -> String {
+        let mut response = format!("HTTP/1.1 {}\r\nConnection: close\r\nContent-Length: {}\r\n", status, body.len());
+        for &(name, value) in headers {
+            response.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        response.push_str("\r\n");
+        response.push_str(body);
+
+        response
+    }
+
+    fn commits_json(sha: &str) -> String {
+        format!(r#"[{{"sha": "{}", "commit": {{
+            "author": {{"name": "Author", "email": "author@example.com", "date": "2017-01-01T10:00:00Z"}},
+            "committer": {{"name": "Committer", "email": "committer@example.com", "date": "2017-01-02T10:00:00Z"}},
+            "message": "a commit"
+        }}}}]"#, sha)
+    }
+
+    fn stub() -> (TcpListener, Client) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let base_url = format!("http://{}/", listener.local_addr().unwrap());
+        let client = Client::new(&Logger::root(Discard, o!()), &base_url, None).unwrap();
+
+        (listener, client)
+    }
+
+    #[test]
+    fn fetches_every_page() {
+        let (listener, client) = stub();
+        let next = format!(r#"<http://{}/page/2>; rel="next""#, listener.local_addr().unwrap());
+        let server = serve(listener, vec![
+            response("200 OK", &[("ETag", "\"first\""), ("Link", next.as_str())], &commits_json("aaa")),
+            response("200 OK", &[("ETag", "\"second\"")], &commits_json("bbb")),
+        ]);
+
+        let since = NaiveDate::from_ymd(2017, 1, 1).and_hms(0, 0, 0);
+        let (commits, etag) = client.commits("rust-lang/rust", "master", &since, None).unwrap().unwrap();
+
+        let shas: Vec<&str> = commits.iter().map(|commit| commit.sha.as_str()).collect();
+        assert_eq!(shas, vec!["aaa", "bbb"]);
+        assert_eq!(etag, Some(String::from("\"first\"")));
+
+        let requests = server.join().unwrap();
+        assert!(requests[0].starts_with("GET /repos/rust-lang/rust/commits?sha=master&since=2017-01-01T00:00:00Z&per_page=100 "));
+        assert!(requests[1].starts_with("GET /page/2 "));
+    }
+
+    #[test]
+    fn stops_when_nothing_changed() {
+        let (listener, client) = stub();
+        let server = serve(listener, vec![response("304 Not Modified", &[], "")]);
+
+        let since = NaiveDate::from_ymd(2017, 1, 1).and_hms(0, 0, 0);
+        let fetched = client.commits("rust-lang/rust", "master", &since, Some("\"first\"")).unwrap();
+
+        assert!(fetched.is_none());
+
+        let requests = server.join().unwrap();
+        assert!(requests[0].to_lowercase().contains("if-none-match: \"first\"\r\n"));
+    }
+}
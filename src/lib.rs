@@ -12,11 +12,27 @@ use diesel::pg::PgConnection;
 
 use dotenv::dotenv;
 
+extern crate r2d2;
+extern crate r2d2_diesel;
+
+use r2d2_diesel::ConnectionManager;
+
 extern crate caseless;
+extern crate chrono;
+#[macro_use]
+extern crate lazy_static;
+extern crate crypto;
+extern crate reqwest;
+extern crate toml;
 extern crate unicode_normalization;
 
 use std::env;
+use std::error::Error;
+use std::time::Duration;
 
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
 extern crate serde_json;
 
 use serde_json::Map;
@@ -29,12 +45,26 @@ pub mod schema;
 pub mod models;
 
 pub mod projects;
+pub mod repositories;
 pub mod releases;
 pub mod commits;
 pub mod authors;
+pub mod dates;
+pub mod git;
+pub mod github;
+pub mod mailmap;
+pub mod config;
+pub mod sync;
+
+pub mod api;
+pub mod pages;
+pub mod webhooks;
 
 use serde_json::value::Value;
 
+use dates::DateRange;
+use models::Project;
+
 pub fn establish_connection() -> PgConnection {
     dotenv().ok();
 
@@ -44,28 +74,133 @@ pub fn establish_connection() -> PgConnection {
         .expect(&format!("Error connecting to {}", database_url))
 }
 
-pub fn scores() -> Vec<Value> {
-    use schema::commits::dsl::*;
+pub type Pool = r2d2::Pool<ConnectionManager<PgConnection>>;
+pub type PooledConnection = r2d2::PooledConnection<ConnectionManager<PgConnection>>;
+
+/// creates a pool of connections to share between requests
+///
+/// `DATABASE_POOL_SIZE` sets how many connections are kept open (10 by
+/// default) and `DATABASE_POOL_TIMEOUT` how many seconds to wait for a free
+/// one before giving up (5 by default).
+pub fn establish_pool() -> Result<Pool, Box<Error>> {
+    dotenv().ok();
+
+    let database_url = env::var("DATABASE_URL")
+        .map_err(|_| "DATABASE_URL must be set")?;
+    let pool_size = match env::var("DATABASE_POOL_SIZE") {
+        Ok(size) => size.parse().map_err(|_| "DATABASE_POOL_SIZE must be a number")?,
+        Err(_) => 10,
+    };
+    let timeout = match env::var("DATABASE_POOL_TIMEOUT") {
+        Ok(timeout) => timeout.parse().map_err(|_| "DATABASE_POOL_TIMEOUT must be a number")?,
+        Err(_) => 5,
+    };
+
+    let config = r2d2::Config::builder()
+        .pool_size(pool_size)
+        .connection_timeout(Duration::from_secs(timeout))
+        .build();
+    let manager = ConnectionManager::<PgConnection>::new(database_url.as_str());
+
+    r2d2::Pool::new(config, manager)
+        .map_err(|e| format!("Error creating a connection pool for {}: {}", database_url, e).into())
+}
+
+/// a single entry of the all-time leaderboard
+#[derive(Debug, Serialize)]
+pub struct Score {
+    #[serde(skip_serializing)]
+    pub author_id: i32,
+    pub rank: u64,
+    pub author: String,
+    pub commits: i64,
+}
+
+/// the leaderboard of a single project, ready for the templates
+pub fn scores(conn: &PgConnection, project: &Project, range: &DateRange) -> Vec<Value> {
+    scores_to_json(ranked_scores(conn, project, range))
+}
+
+/// the leaderboard across every project, ready for the templates
+pub fn organization_scores(conn: &PgConnection, range: &DateRange) -> Vec<Value> {
+    scores_to_json(ranked_organization_scores(conn, range))
+}
+
+fn scores_to_json(scores: Vec<Score>) -> Vec<Value> {
+    scores.into_iter().map(|score| {
+        let mut json_score: Map<String, Value> = Map::new();
+
+        json_score.insert("author_id".to_string(), Value::Number(score.author_id.into()));
+        json_score.insert("rank".to_string(), Value::Number(score.rank.into()));
+        json_score.insert("author".to_string(), Value::String(score.author));
+        json_score.insert("commits".to_string(), Value::Number(score.commits.into()));
+
+        Value::Object(json_score)
+    }).collect()
+}
+
+/// all visible authors of a project, ranked by their number of commits
+///
+/// only commits belonging to one of the project's releases, and authored
+/// within `range`, are counted. co-authors get credit for a commit too, and
+/// backports aren't counted again.
+pub fn ranked_scores(conn: &PgConnection, project: &Project, range: &DateRange) -> Vec<Score> {
+    use schema::contributions::dsl::*;
     use schema::authors::dsl::*;
-    use diesel::expression::dsl::sql;
-    use diesel::types::BigInt;
+    use schema::releases;
+    use diesel::expression::dsl::{any, sql};
+    use diesel::types::{BigInt, Bool};
+
+    let project_releases = releases::table
+        .filter(releases::project_id.eq(project.id))
+        .select(releases::id);
+
+    let scores: Vec<(i32, String, i64)> = contributions.inner_join(authors)
+        .filter(visible.eq(true))
+        // backports were already counted when they landed on master
+        .filter(backport_of.is_null())
+        .filter(release_id.eq(any(project_releases)))
+        .filter(sql::<Bool>(&range.sql_filter()))
+        .select((author_id, name, sql::<BigInt>("COUNT(author_id) AS author_count")))
+        .group_by((author_id, name))
+        .order(sql::<BigInt>("author_count").desc())
+        .load(conn)
+        .unwrap();
+
+    rank(scores)
+}
 
-    let connection = establish_connection();
+/// all visible authors, ranked by their number of commits to any project
+/// within `range`
+pub fn ranked_organization_scores(conn: &PgConnection, range: &DateRange) -> Vec<Score> {
+    use schema::contributions::dsl::*;
+    use schema::authors::dsl::*;
+    use diesel::expression::dsl::sql;
+    use diesel::types::{BigInt, Bool};
 
-    let scores: Vec<_> = commits.inner_join(authors)
+    let scores: Vec<(i32, String, i64)> = contributions.inner_join(authors)
         .filter(visible.eq(true))
-        .select((name, sql::<BigInt>("COUNT(author_id) AS author_count")))
+        // backports were already counted when they landed on master
+        .filter(backport_of.is_null())
+        .filter(sql::<Bool>(&range.sql_filter()))
+        .select((author_id, name, sql::<BigInt>("COUNT(author_id) AS author_count")))
         .group_by((author_id, name))
         .order(sql::<BigInt>("author_count").desc())
-        .load(&connection)
+        .load(conn)
         .unwrap();
 
+    rank(scores)
+}
+
+/// turns (author id, author, commit count) tuples, sorted by count, into a
+/// leaderboard
+fn rank(scores: Vec<(i32, String, i64)>) -> Vec<Score> {
     // these variables are used to calculate the ranking
     let mut rank = 0; // incremented every time
     let mut last_rank = 0; // the current rank
     let mut last_score = 0; // the previous entry's score
 
-    scores.into_iter().map(|(author, score)| {
+    scores.into_iter().map(|(id, author, score)| {
         // we always increment the ranking
         rank += 1;
 
@@ -77,28 +212,24 @@ pub fn scores() -> Vec<Value> {
             last_score = score;
         }
 
-        let mut json_score: Map<String, Value> = Map::new();
-
         // we use last_rank here so that we get duplicate ranks for people
         // with the same number of commits
-        json_score.insert("rank".to_string(), Value::Number(last_rank.into()));
-
-        json_score.insert("author".to_string(), Value::String(author));
-        json_score.insert("commits".to_string(), Value::Number(score.into()));
-
-        Value::Object(json_score)
+        Score {
+            author_id: id,
+            rank: last_rank,
+            author: author,
+            commits: score,
+        }
     }).collect()
 }
 
 /// are we in maintenance mode?
-pub fn in_maintenance() -> bool {
+pub fn in_maintenance(conn: &PgConnection) -> bool {
     use models::Maintenance;
     use schema::maintenances::dsl::*;
 
-    let connection = establish_connection();
-
     let model = maintenances.find(1)
-            .load::<Maintenance>(&connection)
+            .load::<Maintenance>(conn)
             .expect("Error loading maintenance model").remove(0);
     
     model.enabled
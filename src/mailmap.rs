@@ -0,0 +1,388 @@
+//! Resolving authors through a `.mailmap`.
+//!
+//! Git can do this itself with `--use-mailmap`, but commits we get from the
+//! GitHub API never go through git, so every way of importing commits uses
+//! this instead and ends up with the same authors.
+//!
+//! See `git help check-mailmap` for the format.
+
+use models::Repository;
+
+use reqwest;
+
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// how long the server keeps using a mailmap before fetching it again
+const CACHE_SECONDS: u64 = 60 * 60;
+
+lazy_static! {
+    /// mailmaps the server fetched, by where they came from
+    static ref CACHE: Mutex<HashMap<String, (Instant, Arc<Mailmap>)>> = Mutex::new(HashMap::new());
+
+    /// the mailmaps being fetched in the background right now
+    static ref FETCHING: Mutex<HashSet<String>> = Mutex::new(HashSet::new());
+}
+
+/// a single line of a mailmap
+#[derive(Debug, Clone, PartialEq)]
+struct Entry {
+    proper_name: Option<String>,
+    proper_email: Option<String>,
+    commit_name: Option<String>,
+    commit_email: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Mailmap {
+    entries: Vec<Entry>,
+}
+
+impl Mailmap {
+    /// a mailmap that leaves everyone as they are
+    pub fn empty() -> Mailmap {
+        Mailmap::default()
+    }
+
+    /// parses the contents of a mailmap, skipping lines it doesn't understand
+    pub fn parse(contents: &str) -> Mailmap {
+        Mailmap {
+            entries: contents.lines().filter_map(parse_line).collect(),
+        }
+    }
+
+    /// loads a mailmap from a file, or from a url if `source` starts with
+    /// `http://` or `https://`
+    ///
+    /// a missing mailmap is treated as an empty one, since plenty of projects
+    /// don't have one
+    pub fn load(source: &str) -> Result<Mailmap, Box<Error>> {
+        let contents = if source.starts_with("http://") || source.starts_with("https://") {
+            fetch(source)?
+        } else {
+            read(Path::new(source))?
+        };
+
+        Ok(contents.map(|contents| Mailmap::parse(&contents)).unwrap_or_default())
+    }
+
+    /// the mailmap to use for commits read from a checkout of `repository`
+    ///
+    /// this is the repository's configured mailmap, or the `.mailmap` at the
+    /// root of the checkout
+    pub fn for_checkout(repository: &Repository, path: &str) -> Result<Mailmap, Box<Error>> {
+        Mailmap::load_or_checkout(repository.mailmap.as_ref().map(String::as_str), path)
+    }
+
+    /// the mailmap at `source`, or the `.mailmap` at the root of the checkout
+    /// at `path`, for repositories that haven't been saved yet
+    pub fn load_or_checkout(source: Option<&str>, path: &str) -> Result<Mailmap, Box<Error>> {
+        match source {
+            Some(source) => Mailmap::load(source),
+            None => Mailmap::load(&Path::new(path).join(".mailmap").to_string_lossy()),
+        }
+    }
+
+    /// the mailmap to use for commits read from GitHub
+    ///
+    /// this is the repository's configured mailmap, or the `.mailmap` on its
+    /// default branch
+    pub fn for_github(repository: &Repository) -> Result<Mailmap, Box<Error>> {
+        Mailmap::load(&github_source(repository))
+    }
+
+    /// like `for_github`, but never waits on GitHub, so that webhook
+    /// deliveries don't either
+    ///
+    /// the mailmap is fetched in the background, and fetched again once it's
+    /// an hour old, while the old one is still used. returns `None` until it
+    /// has been fetched once.
+    pub fn for_github_cached(repository: &Repository) -> Option<Arc<Mailmap>> {
+        let source = github_source(repository);
+
+        let cached = CACHE.lock().unwrap().get(&source).cloned();
+        match cached {
+            Some((fetched, mailmap)) => {
+                if fetched.elapsed() >= Duration::from_secs(CACHE_SECONDS) {
+                    fetch_in_background(source);
+                }
+                Some(mailmap)
+            }
+            None => {
+                fetch_in_background(source);
+                None
+            }
+        }
+    }
+
+    /// starts fetching the mailmaps of `repositories` in the background, so
+    /// that `for_github_cached` has them by the time they're needed
+    pub fn warm_github_cache(repositories: &[Repository]) {
+        for repository in repositories {
+            fetch_in_background(github_source(repository));
+        }
+    }
+
+    /// the name and email that `name` and `email` should be recorded as
+    ///
+    /// like git, emails and names are matched ignoring case, and lines that
+    /// match both the name and email take precedence over lines that only
+    /// match the email. lines that match the same way are combined, with
+    /// later lines replacing whatever earlier ones set.
+    pub fn resolve(&self, name: &str, email: &str) -> (String, String) {
+        let lowercase_name = name.to_lowercase();
+        let lowercase_email = email.to_lowercase();
+        let matches_email = |entry: &&Entry| entry.commit_email.to_lowercase() == lowercase_email;
+
+        let by_name = combine(self.entries.iter().filter(&matches_email).filter(|entry| {
+            entry.commit_name.as_ref().map_or(false, |commit_name| {
+                commit_name.to_lowercase() == lowercase_name
+            })
+        }));
+        let by_email = || {
+            combine(self.entries.iter().filter(&matches_email).filter(|entry| entry.commit_name.is_none()))
+        };
+
+        match by_name.or_else(by_email) {
+            Some((proper_name, proper_email)) => {
+                (proper_name.unwrap_or_else(|| name.to_string()),
+                 proper_email.unwrap_or_else(|| email.to_string()))
+            }
+            None => (name.to_string(), email.to_string()),
+        }
+    }
+}
+
+/// the proper name and email that `entries` map to together, or `None` if
+/// there aren't any
+fn combine<'a, I: Iterator<Item = &'a Entry>>(entries: I) -> Option<(Option<String>, Option<String>)> {
+    entries.fold(None, |combined, entry| {
+        let (name, email) = combined.unwrap_or((None, None));
+        Some((entry.proper_name.clone().or(name), entry.proper_email.clone().or(email)))
+    })
+}
+
+/// where the mailmap for commits read from GitHub comes from
+fn github_source(repository: &Repository) -> String {
+    match repository.mailmap {
+        Some(ref source) => source.clone(),
+        None => format!("https://raw.githubusercontent.com/{}/{}/.mailmap",
+                        repository.github_name, repository.default_branch),
+    }
+}
+
+/// fetches the mailmap at `source` into the cache on another thread, unless
+/// that's happening already
+///
+/// if the fetch fails, the next delivery that needs the mailmap tries again
+fn fetch_in_background(source: String) {
+    if !FETCHING.lock().unwrap().insert(source.clone()) {
+        return;
+    }
+
+    thread::spawn(move || {
+        if let Ok(mailmap) = Mailmap::load(&source) {
+            CACHE.lock().unwrap().insert(source.clone(), (Instant::now(), Arc::new(mailmap)));
+        }
+
+        FETCHING.lock().unwrap().remove(&source);
+    });
+}
+
+/// parses lines like `Proper Name <proper@email> Commit Name <commit@email>`,
+/// where everything but the last email is optional
+fn parse_line(line: &str) -> Option<Entry> {
+    let line = match line.find('#') {
+        Some(comment) => &line[..comment],
+        None => line,
+    };
+
+    let (first_name, first_email, rest) = match parse_name_and_email(line) {
+        Some(parsed) => parsed,
+        None => return None,
+    };
+
+    match parse_name_and_email(rest) {
+        // `Proper Name <commit@email>`
+        None => {
+            first_name.map(|name| Entry {
+                proper_name: Some(name),
+                proper_email: None,
+                commit_name: None,
+                commit_email: first_email,
+            })
+        }
+        Some((second_name, second_email, _)) => {
+            Some(Entry {
+                proper_name: first_name,
+                proper_email: Some(first_email),
+                commit_name: second_name,
+                commit_email: second_email,
+            })
+        }
+    }
+}
+
+/// splits `Name <email> rest` into its parts, where the name may be missing
+fn parse_name_and_email(text: &str) -> Option<(Option<String>, String, &str)> {
+    let start = match text.find('<') {
+        Some(start) => start,
+        None => return None,
+    };
+    let end = match text[start..].find('>') {
+        Some(end) => start + end,
+        None => return None,
+    };
+
+    let name = text[..start].trim();
+    let name = if name.is_empty() { None } else { Some(name.to_string()) };
+
+    Some((name, text[start + 1..end].trim().to_string(), &text[end + 1..]))
+}
+
+fn read(path: &Path) -> Result<Option<String>, Box<Error>> {
+    let mut contents = String::new();
+
+    match File::open(path).and_then(|mut file| file.read_to_string(&mut contents)) {
+        Ok(_) => Ok(Some(contents)),
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(format!("Error reading mailmap {}: {}", path.display(), e).into()),
+    }
+}
+
+fn fetch(url: &str) -> Result<Option<String>, Box<Error>> {
+    let mut resp = reqwest::get(url)
+        .map_err(|e| format!("Error fetching mailmap {}: {}", url, e))?;
+
+    if !resp.status().is_success() {
+        return Ok(None);
+    }
+
+    let mut contents = String::new();
+    resp.read_to_string(&mut contents)
+        .map_err(|e| format!("Error reading mailmap {}: {}", url, e))?;
+
+    Ok(Some(contents))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Entry, Mailmap};
+
+    fn resolve(mailmap: &str, name: &str, email: &str) -> (String, String) {
+        Mailmap::parse(mailmap).resolve(name, email)
+    }
+
+    fn person(name: &str, email: &str) -> (String, String) {
+        (name.to_string(), email.to_string())
+    }
+
+    #[test]
+    fn replaces_the_name() {
+        let mailmap = "Jane Doe <jane@example.com>";
+
+        assert_eq!(Mailmap::parse(mailmap).entries, vec![Entry {
+            proper_name: Some(String::from("Jane Doe")),
+            proper_email: None,
+            commit_name: None,
+            commit_email: String::from("jane@example.com"),
+        }]);
+        assert_eq!(resolve(mailmap, "jdoe", "jane@example.com"), person("Jane Doe", "jane@example.com"));
+    }
+
+    #[test]
+    fn replaces_the_email() {
+        let mailmap = "<jane@example.com> <jane@laptop.local>";
+
+        assert_eq!(Mailmap::parse(mailmap).entries, vec![Entry {
+            proper_name: None,
+            proper_email: Some(String::from("jane@example.com")),
+            commit_name: None,
+            commit_email: String::from("jane@laptop.local"),
+        }]);
+        assert_eq!(resolve(mailmap, "jdoe", "jane@laptop.local"), person("jdoe", "jane@example.com"));
+    }
+
+    #[test]
+    fn replaces_the_name_and_email() {
+        let mailmap = "Jane Doe <jane@example.com> <jane@laptop.local>";
+
+        assert_eq!(Mailmap::parse(mailmap).entries, vec![Entry {
+            proper_name: Some(String::from("Jane Doe")),
+            proper_email: Some(String::from("jane@example.com")),
+            commit_name: None,
+            commit_email: String::from("jane@laptop.local"),
+        }]);
+        assert_eq!(resolve(mailmap, "jdoe", "jane@laptop.local"), person("Jane Doe", "jane@example.com"));
+    }
+
+    #[test]
+    fn replaces_the_name_and_email_of_one_commit_name() {
+        let mailmap = "Jane Doe <jane@example.com> jdoe <shared@example.com>";
+
+        assert_eq!(Mailmap::parse(mailmap).entries, vec![Entry {
+            proper_name: Some(String::from("Jane Doe")),
+            proper_email: Some(String::from("jane@example.com")),
+            commit_name: Some(String::from("jdoe")),
+            commit_email: String::from("shared@example.com"),
+        }]);
+        assert_eq!(resolve(mailmap, "jdoe", "shared@example.com"), person("Jane Doe", "jane@example.com"));
+        assert_eq!(resolve(mailmap, "jsmith", "shared@example.com"), person("jsmith", "shared@example.com"));
+    }
+
+    #[test]
+    fn matches_ignoring_case() {
+        let mailmap = "Jane Doe <jane@example.com> JDoe <Jane@Laptop.local>";
+
+        assert_eq!(resolve(mailmap, "jdoe", "jane@laptop.LOCAL"), person("Jane Doe", "jane@example.com"));
+    }
+
+    #[test]
+    fn skips_comments_and_lines_it_doesnt_understand() {
+        let mailmap = "# the people who work here\n\
+                       \n\
+                       <nobody@example.com>\n\
+                       Jane Doe <jane@example.com> # herself\n\
+                       not a mailmap line";
+
+        assert_eq!(Mailmap::parse(mailmap).entries.len(), 1);
+        assert_eq!(resolve(mailmap, "jdoe", "jane@example.com"), person("Jane Doe", "jane@example.com"));
+    }
+
+    #[test]
+    fn leaves_everyone_else_alone() {
+        let mailmap = "Jane Doe <jane@example.com> <jane@laptop.local>";
+
+        assert_eq!(resolve(mailmap, "John Doe", "john@example.com"), person("John Doe", "john@example.com"));
+        assert_eq!(Mailmap::empty().resolve("jdoe", "jane@laptop.local"), person("jdoe", "jane@laptop.local"));
+    }
+
+    #[test]
+    fn prefers_lines_that_match_the_name_too() {
+        // whichever comes first
+        let by_name_first = "Jane Doe <jane@example.com> jdoe <shared@example.com>\n\
+                             Build Bot <bot@example.com> <shared@example.com>";
+        let by_email_first = "Build Bot <bot@example.com> <shared@example.com>\n\
+                              Jane Doe <jane@example.com> jdoe <shared@example.com>";
+
+        for mailmap in &[by_name_first, by_email_first] {
+            assert_eq!(resolve(mailmap, "jdoe", "shared@example.com"), person("Jane Doe", "jane@example.com"));
+            assert_eq!(resolve(mailmap, "ci", "shared@example.com"), person("Build Bot", "bot@example.com"));
+        }
+    }
+
+    #[test]
+    fn combines_lines_that_match_the_same_way() {
+        let mailmap = "Jane Doe <jane@laptop.local>\n\
+                       <jane@example.com> <jane@laptop.local>\n\
+                       Jane Q. Doe <jane@laptop.local>";
+
+        assert_eq!(resolve(mailmap, "jdoe", "jane@laptop.local"), person("Jane Q. Doe", "jane@example.com"));
+    }
+}
@@ -1,5 +1,7 @@
 extern crate thanks;
 
+extern crate diesel;
+
 extern crate dotenv;
 
 extern crate futures;
@@ -8,10 +10,18 @@ extern crate handlebars;
 
 extern crate hyper;
 
+#[macro_use]
+extern crate lazy_static;
+
 extern crate regex;
 
+extern crate serde;
 extern crate serde_json;
 
+#[macro_use]
+extern crate slog;
+extern crate slog_term;
+
 extern crate sparkles;
 
 use sparkles::Request;
@@ -20,17 +30,68 @@ use sparkles::Error;
 use sparkles::ResponseBuilder;
 use sparkles::Status;
 
-use futures::BoxFuture;
+use futures::{BoxFuture, Future, Stream};
 
 use regex::Captures;
 
 use std::env;
+use std::process;
+use std::sync::RwLock;
 
+use serde::Serialize;
 use serde_json::value::Value;
 
+use slog::DrainExt;
+
+use thanks::api;
+use thanks::dates::DateRange;
+use thanks::mailmap::Mailmap;
+use thanks::models::Project;
+use thanks::pages::{self, Page};
+use thanks::webhooks::WebhookError;
+
+use diesel::pg::PgConnection;
+
+lazy_static! {
+    /// the connections shared by every request, set up by `main` before the
+    /// server starts
+    static ref POOL: RwLock<Option<thanks::Pool>> = RwLock::new(None);
+
+    static ref LOG: slog::Logger = slog::Logger::root(slog_term::streamer().full().build().fuse(), o!("version" => env!("CARGO_PKG_VERSION")));
+}
+
+/// gets a connection from the pool, or returns `$unavailable` from the
+/// handler if none frees up in time
+macro_rules! connection {
+    ($unavailable:expr) => {
+        match pooled_connection() {
+            Some(connection) => connection,
+            None => return $unavailable,
+        }
+    }
+}
+
 fn main() {
     dotenv::dotenv().ok();
 
+    let pool = match thanks::establish_pool() {
+        Ok(pool) => pool,
+        Err(e) => {
+            error!(LOG, "{}", e);
+            process::exit(1);
+        }
+    };
+    // webhook deliveries don't wait for mailmaps to be fetched, so they start
+    // being fetched now rather than when the first push comes in
+    if let Ok(connection) = pool.get() {
+        match thanks::repositories::with_webhook_secret(&connection) {
+            Ok(repositories) => Mailmap::warm_github_cache(&repositories),
+            Err(e) => warn!(LOG, "Error loading the repositories that receive webhooks: {}", e),
+        }
+    }
+
+    *POOL.write().unwrap() = Some(pool);
+
     let addr = format!("0.0.0.0:{}",
                        env::args().nth(1).unwrap_or(String::from("1337")))
         .parse()
@@ -42,86 +103,473 @@ fn main() {
 
     server.add_route("/about", about);
 
-    server.add_route("/rust/all-time", all_time);
+    server.add_route("/all-time", organization_all_time);
+
+    server.add_route("/api/v1/projects", api_projects);
+
+    server.add_route("/api/v1/all-time", api_organization_all_time);
+
+    server.add_route("/webhooks/github", github_webhook);
 
-    server.add_regex_route("/([^/]+)/(.+)", release);
+    server.add_regex_route(r"^/api/v1/all-time/([0-9-]*\.\.[0-9-]*)$", api_organization_window);
+
+    server.add_regex_route("^/api/v1/([^/]+)$", api_releases);
+
+    server.add_regex_route(r"^/api/v1/([^/]+)/all-time(?:/([0-9-]*\.\.[0-9-]*))?$", api_all_time);
+
+    server.add_regex_route("^/api/v1/([^/]+)/authors/([0-9]+)$", api_author);
+
+    server.add_regex_route(r"^/api/v1/([^/]+)/([^/]+)\.\.\.([^/]+)(?:/([0-9-]*\.\.[0-9-]*))?$", api_range);
+
+    server.add_regex_route(r"^/api/v1/([^/]+)/([^/]+)(?:/([0-9-]*\.\.[0-9-]*))?$", api_release);
+
+    server.add_regex_route("^/([^/]+)/?$", project);
+
+    server.add_regex_route(r"^/all-time/([0-9-]*\.\.[0-9-]*)$", organization_window);
+
+    server.add_regex_route(r"^/([^/]+)/all-time(?:/([0-9-]*\.\.[0-9-]*))?$", all_time);
+
+    server.add_regex_route(r"^/([^/]+)/feed\.xml$", feed);
+
+    server.add_regex_route("^/([^/]+)/authors/([0-9]+)$", author);
+
+    server.add_regex_route(r"^/([^/]+)/([^/]+)\.\.\.([^/]+)(?:/([0-9-]*\.\.[0-9-]*))?$", range);
+
+    server.add_regex_route(r"^/([^/]+)/(.+?)(?:/([0-9-]*\.\.[0-9-]*))?$", release);
 
     server.run(&addr);
 }
 
-fn root(_: Request) -> BoxFuture<Response, Error> {
-    let mut res = ResponseBuilder::new();
-    res.with_template("index".to_string());
+/// a connection from the pool, or `None` if none frees up in time
+fn pooled_connection() -> Option<thanks::PooledConnection> {
+    let pool = POOL.read().unwrap();
 
-    res.data.insert("maintenance".to_string(),
-                Value::Bool(thanks::in_maintenance()));
+    pool.as_ref().and_then(|pool| pool.get().ok())
+}
+
+/// looks up the project named by the first capture of a route
+fn find_project(connection: &PgConnection, cap: &Captures) -> Option<Project> {
+    let url_path = cap.get(1).unwrap().as_str();
 
-    res.data.insert("releases".to_string(),
-                Value::Array(thanks::releases::all()));
+    thanks::projects::find_by_url_path(connection, url_path)
+}
+
+/// parses the window of time a route may end with
+///
+/// returns `None` if it isn't a valid range
+fn date_range(cap: &Captures, group: usize) -> Option<DateRange> {
+    match cap.get(group) {
+        Some(range) => DateRange::parse(range.as_str()),
+        None => Some(DateRange::all()),
+    }
+}
+
+/// renders a page through the response's template
+fn render(mut res: ResponseBuilder, page: Page) -> BoxFuture<Response, Error> {
+    for (key, value) in page {
+        res.data.insert(key, value);
+    }
 
     res.with_status(Status::Ok);
 
     res.to_response().into_future()
 }
 
+fn not_found(mut res: ResponseBuilder) -> BoxFuture<Response, Error> {
+    res.with_status(Status::NotFound);
+
+    res.to_response().into_future()
+}
+
+/// returned when no database connection frees up in time
+fn unavailable(mut res: ResponseBuilder) -> BoxFuture<Response, Error> {
+    res.with_status(Status::ServiceUnavailable);
+
+    res.to_response().into_future()
+}
+
+fn root(_: Request) -> BoxFuture<Response, Error> {
+    let mut res = ResponseBuilder::new();
+    res.with_template("index".to_string());
+
+    let connection = connection!(unavailable(res));
+
+    render(res, pages::index(&connection))
+}
+
 fn about(_: Request) -> BoxFuture<Response, Error> {
     let mut res = ResponseBuilder::new();
     res.with_template("about".to_string());
 
-    res.data.insert("maintenance".to_string(),
-                Value::Bool(thanks::in_maintenance()));
+    let connection = connection!(unavailable(res));
 
-    res.with_status(Status::Ok);
+    render(res, pages::about(&connection))
+}
 
-    res.to_response().into_future()
+fn project(_: &Request, cap: Captures) -> BoxFuture<Response, Error> {
+    let mut res = ResponseBuilder::new();
+    res.with_template("project".to_string());
+
+    let connection = connection!(unavailable(res));
+
+    match find_project(&connection, &cap) {
+        Some(project) => render(res, pages::project(&connection, &project)),
+        None => not_found(res),
+    }
 }
 
-fn all_time(_: Request) -> BoxFuture<Response, Error> {
+fn all_time(_: &Request, cap: Captures) -> BoxFuture<Response, Error> {
     let mut res = ResponseBuilder::new();
     res.with_template("all-time".to_string());
 
-    res.data.insert("maintenance".to_string(),
-                Value::Bool(thanks::in_maintenance()));
+    let connection = connection!(unavailable(res));
 
-    let scores = thanks::scores();
+    let project = match find_project(&connection, &cap) {
+        Some(project) => project,
+        None => return not_found(res),
+    };
 
-    res.data.insert("release".to_string(),
-                Value::String(String::from("all-time")));
-    res.data.insert("count".to_string(), Value::Number((scores.len() as u64).into()));
-    res.data.insert("scores".to_string(), Value::Array(scores));
+    match date_range(&cap, 2) {
+        Some(range) => render(res, pages::all_time(&connection, &project, &range)),
+        None => not_found(res),
+    }
+}
 
-    res.with_status(Status::Ok);
+fn organization_all_time(_: Request) -> BoxFuture<Response, Error> {
+    organization_leaderboard(Some(DateRange::all()))
+}
 
-    res.to_response().into_future()
+fn organization_window(_: &Request, cap: Captures) -> BoxFuture<Response, Error> {
+    organization_leaderboard(date_range(&cap, 1))
+}
+
+fn organization_leaderboard(range: Option<DateRange>) -> BoxFuture<Response, Error> {
+    let mut res = ResponseBuilder::new();
+    res.with_template("all-time".to_string());
+
+    let connection = connection!(unavailable(res));
+
+    match range {
+        Some(range) => render(res, pages::organization_all_time(&connection, &range)),
+        None => not_found(res),
+    }
+}
+
+fn author(_: &Request, cap: Captures) -> BoxFuture<Response, Error> {
+    let mut res = ResponseBuilder::new();
+    res.with_template("author".to_string());
+
+    let connection = connection!(unavailable(res));
+
+    let project = match find_project(&connection, &cap) {
+        Some(project) => project,
+        None => return not_found(res),
+    };
+
+    let author_id = match cap.get(2).unwrap().as_str().parse() {
+        Ok(author_id) => author_id,
+        Err(_) => return not_found(res),
+    };
+
+    match pages::author(&connection, &project, author_id) {
+        Some(page) => render(res, page),
+        None => not_found(res),
+    }
+}
+
+fn feed(_: &Request, cap: Captures) -> BoxFuture<Response, Error> {
+    let mut res = ResponseBuilder::new();
+    res.with_template("feed".to_string());
+
+    let connection = connection!(unavailable(res));
+
+    let project = match find_project(&connection, &cap) {
+        Some(project) => project,
+        None => return not_found(res),
+    };
+
+    res.with_header("Content-Type".to_string(), "application/atom+xml; charset=utf-8".to_string());
+
+    render(res, pages::feed(&connection, &project))
+}
+
+fn range(_: &Request, cap: Captures) -> BoxFuture<Response, Error> {
+    let mut res = ResponseBuilder::new();
+    res.with_template("range".to_string());
+
+    let connection = connection!(unavailable(res));
+
+    let project = match find_project(&connection, &cap) {
+        Some(project) => project,
+        None => return not_found(res),
+    };
+
+    let from = cap.get(2).unwrap().as_str();
+    let to = cap.get(3).unwrap().as_str();
+
+    let range = match date_range(&cap, 4) {
+        Some(range) => range,
+        None => return not_found(res),
+    };
+
+    match pages::range(&connection, &project, from, to, &range) {
+        Some(page) => render(res, page),
+        None => not_found(res),
+    }
 }
 
 fn release(_: &Request, cap: Captures) -> BoxFuture<Response, Error> {
     let mut res = ResponseBuilder::new();
     res.with_template("release".to_string());
 
-    res.data.insert("maintenance".to_string(),
-                Value::Bool(thanks::in_maintenance()));
+    let connection = connection!(unavailable(res));
 
-    let project = cap.get(1).unwrap();
-    let project = project.as_str();
+    let project = match find_project(&connection, &cap) {
+        Some(project) => project,
+        None => return not_found(res),
+    };
 
     let release_name = cap.get(2).unwrap();
     let release_name = release_name.as_str();
 
-    res.data.insert("release".to_string(), Value::String(release_name.to_string()));
+    let range = match date_range(&cap, 3) {
+        Some(range) => range,
+        None => return not_found(res),
+    };
+
+    match pages::release(&connection, &project, release_name, &range) {
+        Some(page) => render(res, page),
+        None => not_found(res),
+    }
+}
+
+/// serializes `body` through the `json` template
+///
+/// every API response goes through here so that they all get the same
+/// content type and CORS headers
+fn json_response<T: Serialize>(status: Status, body: &T) -> BoxFuture<Response, Error> {
+    let mut res = ResponseBuilder::new();
+    res.with_template("json".to_string());
+
+    res.with_header("Content-Type".to_string(), "application/json; charset=utf-8".to_string());
+    res.with_header("Access-Control-Allow-Origin".to_string(), "*".to_string());
+
+    let json = serde_json::to_string(body).expect("Error serializing API response");
+    res.data.insert("json".to_string(), Value::String(json));
+
+    res.with_status(status);
+
+    res.to_response().into_future()
+}
+
+/// the 503 returned when no database connection frees up in time
+fn api_unavailable() -> BoxFuture<Response, Error> {
+    json_response(Status::ServiceUnavailable, &api::ApiError::new("too many requests, try again later"))
+}
+
+fn api_projects(_: Request) -> BoxFuture<Response, Error> {
+    let connection = connection!(api_unavailable());
+
+    let projects = thanks::projects::all(&connection)
+        .iter()
+        .map(api::ProjectInfo::from)
+        .collect();
+
+    json_response(Status::Ok, &api::Projects { projects: projects })
+}
+
+/// the 404 returned for unknown projects
+fn api_project_not_found(cap: &Captures) -> BoxFuture<Response, Error> {
+    let message = format!("no project {} found", cap.get(1).unwrap().as_str());
+
+    json_response(Status::NotFound, &api::ApiError::new(message))
+}
 
-    let names = thanks::releases::contributors(project, release_name);
+fn api_releases(_: &Request, cap: Captures) -> BoxFuture<Response, Error> {
+    let connection = connection!(api_unavailable());
 
-    match names {
-        Some(names) => {
-            res.data.insert("count".to_string(), Value::Number((names.len() as u64).into()));
-            res.data.insert("names".to_string(), Value::Array(names));
-            res.with_status(Status::Ok);
+    let project = match find_project(&connection, &cap) {
+        Some(project) => project,
+        None => return api_project_not_found(&cap),
+    };
+
+    let releases = api::Releases {
+        releases: thanks::releases::versions(&connection, &project),
+        project: project.name,
+    };
+
+    json_response(Status::Ok, &releases)
+}
+
+fn api_all_time(_: &Request, cap: Captures) -> BoxFuture<Response, Error> {
+    let connection = connection!(api_unavailable());
+
+    let project = match find_project(&connection, &cap) {
+        Some(project) => project,
+        None => return api_project_not_found(&cap),
+    };
+
+    let range = match date_range(&cap, 2) {
+        Some(range) => range,
+        None => return api_invalid_range(&cap, 2),
+    };
+
+    let scores = thanks::ranked_scores(&connection, &project, &range);
+
+    json_response(Status::Ok, &api::Scores::new(Some(project.name), &range, scores))
+}
+
+fn api_organization_all_time(_: Request) -> BoxFuture<Response, Error> {
+    api_organization_leaderboard(DateRange::all())
+}
+
+fn api_organization_window(_: &Request, cap: Captures) -> BoxFuture<Response, Error> {
+    match date_range(&cap, 1) {
+        Some(range) => api_organization_leaderboard(range),
+        None => api_invalid_range(&cap, 1),
+    }
+}
+
+fn api_organization_leaderboard(range: DateRange) -> BoxFuture<Response, Error> {
+    let connection = connection!(api_unavailable());
+
+    let scores = thanks::ranked_organization_scores(&connection, &range);
+
+    json_response(Status::Ok, &api::Scores::new(None, &range, scores))
+}
+
+/// the 404 returned for windows of time that can't be parsed
+fn api_invalid_range(cap: &Captures, group: usize) -> BoxFuture<Response, Error> {
+    let message = format!("invalid date range {}, expected YYYY-MM-DD..YYYY-MM-DD",
+                          cap.get(group).unwrap().as_str());
+
+    json_response(Status::NotFound, &api::ApiError::new(message))
+}
+
+fn api_author(_: &Request, cap: Captures) -> BoxFuture<Response, Error> {
+    let connection = connection!(api_unavailable());
+
+    let project = match find_project(&connection, &cap) {
+        Some(project) => project,
+        None => return api_project_not_found(&cap),
+    };
+    let author = cap.get(2).unwrap().as_str();
+
+    match author.parse().ok().and_then(|id| thanks::authors::profile(&connection, &project, id)) {
+        Some(profile) => json_response(Status::Ok, &profile),
+        None => {
+            let message = format!("no author {} found for project {}", author, project.name);
+            json_response(Status::NotFound, &api::ApiError::new(message))
+        }
+    }
+}
+
+fn api_range(_: &Request, cap: Captures) -> BoxFuture<Response, Error> {
+    let connection = connection!(api_unavailable());
+
+    let project = match find_project(&connection, &cap) {
+        Some(project) => project,
+        None => return api_project_not_found(&cap),
+    };
+    let from = cap.get(2).unwrap().as_str();
+    let to = cap.get(3).unwrap().as_str();
+
+    let range = match date_range(&cap, 4) {
+        Some(range) => range,
+        None => return api_invalid_range(&cap, 4),
+    };
+
+    match thanks::releases::range_contributors(&connection, &project, from, to, &range) {
+        Some(contributors) => {
+            json_response(Status::Ok, &api::RangeContributors {
+                project: project.name,
+                from: from.to_string(),
+                to: to.to_string(),
+                since: range.since.map(|date| date.to_string()),
+                until: range.until.map(|date| date.to_string()),
+                count: contributors.len(),
+                contributors: contributors,
+            })
+        }
+        None => {
+            let message = format!("releases {} and {} must both exist in project {}", from, to, project.name);
+            json_response(Status::NotFound, &api::ApiError::new(message))
+        }
+    }
+}
+
+fn api_release(_: &Request, cap: Captures) -> BoxFuture<Response, Error> {
+    let connection = connection!(api_unavailable());
+
+    let project = match find_project(&connection, &cap) {
+        Some(project) => project,
+        None => return api_project_not_found(&cap),
+    };
+    let release_name = cap.get(2).unwrap().as_str();
+
+    let range = match date_range(&cap, 3) {
+        Some(range) => range,
+        None => return api_invalid_range(&cap, 3),
+    };
+
+    match thanks::releases::contributor_list(&connection, &project, release_name, &range) {
+        Some(contributors) => {
+            let first_time = contributors.iter()
+                .filter(|c| c.first_time)
+                .map(|c| c.name.clone())
+                .collect();
+
+            json_response(Status::Ok, &api::Contributors {
+                project: project.name,
+                release: release_name.to_string(),
+                since: range.since.map(|date| date.to_string()),
+                until: range.until.map(|date| date.to_string()),
+                count: contributors.len(),
+                contributors: contributors.into_iter().map(|c| c.name).collect(),
+                first_time_contributors: first_time,
+            })
         }
         None => {
-            res.with_status(Status::NotFound);
+            let message = format!("no release {} found for project {}", release_name, project.name);
+            json_response(Status::NotFound, &api::ApiError::new(message))
         }
     }
+}
 
-    res.to_response().into_future()
+/// GitHub's push webhook
+///
+/// see the `webhooks` module for what it does with deliveries
+fn github_webhook(req: Request) -> BoxFuture<Response, Error> {
+    let event = header(&req, "X-GitHub-Event").unwrap_or_default();
+    let signature = header(&req, "X-Hub-Signature");
+
+    req.body().concat().then(move |body| {
+        let body = match body {
+            Ok(body) => body,
+            Err(_) => return json_response(Status::BadRequest, &api::ApiError::new("couldn't read the body")),
+        };
+
+        let connection = connection!(api_unavailable());
+
+        match thanks::webhooks::receive(&connection, &LOG, &event, signature.as_ref().map(String::as_str), &body) {
+            Ok(delivery) => json_response(Status::Ok, &delivery),
+            Err(WebhookError::BadPayload) => {
+                json_response(Status::BadRequest, &api::ApiError::new("invalid payload"))
+            }
+            Err(WebhookError::BadSignature) => {
+                json_response(Status::Unauthorized, &api::ApiError::new("invalid signature"))
+            }
+            Err(WebhookError::Failed(e)) => {
+                error!(LOG, "Error recording a push: {}", e);
+                json_response(Status::InternalServerError, &api::ApiError::new("error recording the push"))
+            }
+        }
+    }).boxed()
+}
+
+/// the first value of a request header
+fn header(req: &Request, name: &str) -> Option<String> {
+    req.headers().get_raw(name)
+        .and_then(|values| values.one())
+        .map(|value| String::from_utf8_lossy(value).into_owned())
 }
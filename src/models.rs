@@ -1,10 +1,37 @@
+use chrono::NaiveDateTime;
+
 #[derive(Debug,Identifiable,Queryable,Associations)]
 #[has_many(releases)]
+#[has_many(repositories)]
 pub struct Project {
     pub id: i32,
     pub name: String,
     pub url_path: String,
+    /// the repository the project is named after
+    pub github_name: String,
+}
+
+/// one of the repositories a project's releases are made of
+#[derive(Debug,Identifiable,Queryable,Associations)]
+#[belongs_to(Project)]
+#[table_name="repositories"]
+pub struct Repository {
+    pub id: i32,
+    pub project_id: i32,
     pub github_name: String,
+    /// how release tags are named, with `{version}` standing for the version
+    pub tag_pattern: String,
+    /// a path or url to read the repository's mailmap from, instead of the
+    /// `.mailmap` in it
+    pub mailmap: Option<String>,
+    /// the secret GitHub signs webhook deliveries with; without one, they're
+    /// refused
+    pub webhook_secret: Option<String>,
+    /// when a webhook asked for the repository's commits to be fetched from
+    /// the API, if they haven't been since
+    pub sync_requested_at: Option<NaiveDateTime>,
+    /// the branch the `master` release follows
+    pub default_branch: String,
 }
 
 #[derive(Debug,Identifiable,Queryable,Associations)]
@@ -15,25 +42,59 @@ pub struct Commit {
     pub sha: String,
     pub release_id: i32,
     pub author_id: i32,
+    pub authored_at: Option<NaiveDateTime>,
+    pub committed_at: Option<NaiveDateTime>,
+    /// the commit this one was cherry-picked from
+    pub backport_of: Option<String>,
 }
 
 #[derive(Debug,Identifiable,Queryable,Associations)]
 #[has_many(commits)]
+#[has_many(contributions)]
 #[belongs_to(Project)]
 pub struct Release {
     pub id: i32,
     pub version: String,
     pub project_id: i32,
     pub visible: bool,
+    /// when the release was first made visible, if it ever was
+    pub published_at: Option<NaiveDateTime>,
+    /// overrides which release this one follows
+    ///
+    /// a release normally contains the commits that no earlier release
+    /// contains; with this set, it contains the ones this release doesn't
+    pub previous_version: Option<String>,
 }
 
 #[derive(Debug,Identifiable,Queryable,Associations)]
 #[has_many(commits)]
+#[has_many(contributions)]
 pub struct Author {
     pub id: i32,
     pub name: String,
     pub email: String,
+    /// false if the author, or anyone merged into them, opted out
     pub visible: bool,
+    /// whether the author opted out themselves
+    pub opted_out: bool,
+}
+
+use super::schema::contributions;
+
+/// a commit, credited to one of its authors
+///
+/// commits are credited to their author and to everyone named in their
+/// `Co-authored-by:` trailers
+#[derive(Debug,Queryable,Associations)]
+#[belongs_to(Release)]
+#[belongs_to(Author)]
+#[table_name="contributions"]
+pub struct Contribution {
+    pub sha: String,
+    pub release_id: i32,
+    pub author_id: i32,
+    pub authored_at: Option<NaiveDateTime>,
+    pub backport_of: Option<String>,
 }
 
 use super::schema::projects;
@@ -46,6 +107,50 @@ pub struct NewProject<'a> {
     pub github_name: &'a str,
 }
 
+use super::schema::repositories;
+
+#[derive(Insertable)]
+#[table_name="repositories"]
+pub struct NewRepository<'a> {
+    pub project_id: i32,
+    pub github_name: &'a str,
+    pub tag_pattern: &'a str,
+    pub mailmap: Option<&'a str>,
+    pub default_branch: &'a str,
+}
+
+use super::schema::repository_tags;
+
+/// the tag a release has in a repository, when it doesn't follow the
+/// repository's tag pattern
+#[derive(Debug,Queryable,Insertable)]
+#[table_name="repository_tags"]
+pub struct RepositoryTag {
+    pub repository_id: i32,
+    pub release_id: i32,
+    pub tag: String,
+}
+
+use super::schema::assigned_releases;
+
+/// a release whose commits were assigned from a repository
+#[derive(Debug,Queryable,Insertable)]
+#[table_name="assigned_releases"]
+pub struct AssignedRelease {
+    pub repository_id: i32,
+    pub release_id: i32,
+}
+
+use super::schema::bots;
+
+/// an author whose commits aren't credited in a project, because they're a bot
+#[derive(Debug,Queryable,Insertable)]
+#[table_name="bots"]
+pub struct Bot {
+    pub project_id: i32,
+    pub email: String,
+}
+
 use super::schema::commits;
 
 #[derive(Insertable)]
@@ -54,6 +159,17 @@ pub struct NewCommit<'a> {
     pub sha: &'a str,
     pub release_id: i32,
     pub author_id: i32,
+    pub authored_at: Option<NaiveDateTime>,
+    pub committed_at: Option<NaiveDateTime>,
+}
+
+use super::schema::co_authors;
+
+#[derive(Insertable)]
+#[table_name="co_authors"]
+pub struct NewCoAuthor<'a> {
+    pub sha: &'a str,
+    pub author_id: i32,
 }
 
 use super::schema::releases;
@@ -75,6 +191,37 @@ pub struct NewAuthor<'a> {
     pub email: &'a str,
 }
 
+use super::schema::author_aliases;
+
+/// records that `alias_id` is the same person as `canonical_id`
+///
+/// canonical authors are never aliases themselves
+#[derive(Debug,Identifiable,Queryable,Insertable)]
+#[table_name="author_aliases"]
+#[primary_key(alias_id)]
+pub struct AuthorAlias {
+    pub alias_id: i32,
+    pub canonical_id: i32,
+}
+
+use super::schema::github_syncs;
+
+/// where the last sync of a repository with GitHub left off
+///
+/// the next sync asks for the commits since a while before `synced_at`, and
+/// if that's the same request as last time, only for ones that changed its
+/// `etag`
+#[derive(Debug,Identifiable,Queryable,Insertable)]
+#[table_name="github_syncs"]
+#[primary_key(repository)]
+pub struct GitHubSync {
+    pub repository: String,
+    pub since: NaiveDateTime,
+    pub etag: Option<String>,
+    /// when the last successful sync started
+    pub synced_at: NaiveDateTime,
+}
+
 use super::schema::maintenances;
 
 #[derive(Debug, Identifiable, Queryable)]
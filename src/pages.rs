@@ -0,0 +1,196 @@
+//! The data behind every page of the site.
+//!
+//! Both the web server and the `export` binary render these through the
+//! templates, so a static copy of the site looks exactly like the live one.
+
+use dates::DateRange;
+use models::Project;
+
+use chrono::{NaiveDateTime, UTC};
+
+use diesel::pg::PgConnection;
+
+use serde_json;
+use serde_json::Map;
+use serde_json::value::Value;
+
+use std::env;
+
+pub type Page = Map<String, Value>;
+
+/// how many releases the feeds contain
+const FEED_LENGTH: i64 = 20;
+
+/// the data every page needs
+pub fn base(conn: &PgConnection, project: Option<&Project>) -> Page {
+    let mut page = Page::new();
+
+    page.insert("maintenance".to_string(), Value::Bool(::in_maintenance(conn)));
+
+    if let Some(project) = project {
+        page.insert("project".to_string(), Value::String(project.name.clone()));
+        page.insert("url_path".to_string(), Value::String(project.url_path.clone()));
+    }
+
+    page
+}
+
+pub fn index(conn: &PgConnection) -> Page {
+    let mut page = base(conn, None);
+
+    let projects = ::projects::all(conn).into_iter().map(|project| {
+        let mut json_project: Map<String, Value> = Map::new();
+
+        json_project.insert("name".to_string(), Value::String(project.name));
+        json_project.insert("url_path".to_string(), Value::String(project.url_path));
+
+        Value::Object(json_project)
+    }).collect();
+
+    page.insert("projects".to_string(), Value::Array(projects));
+
+    page
+}
+
+pub fn about(conn: &PgConnection) -> Page {
+    base(conn, None)
+}
+
+pub fn project(conn: &PgConnection, project: &Project) -> Page {
+    let mut page = base(conn, Some(project));
+
+    page.insert("releases".to_string(),
+                Value::Array(::releases::all(conn, project)));
+
+    page
+}
+
+pub fn all_time(conn: &PgConnection, project: &Project, range: &DateRange) -> Page {
+    let mut page = base(conn, Some(project));
+
+    let scores = ::scores(conn, project, range);
+
+    insert_range(&mut page, range);
+    page.insert("release".to_string(),
+                Value::String(String::from("all-time")));
+    page.insert("count".to_string(), Value::Number((scores.len() as u64).into()));
+    page.insert("scores".to_string(), Value::Array(scores));
+
+    page
+}
+
+pub fn organization_all_time(conn: &PgConnection, range: &DateRange) -> Page {
+    let mut page = base(conn, None);
+
+    let scores = ::organization_scores(conn, range);
+
+    insert_range(&mut page, range);
+    page.insert("organization".to_string(), Value::Bool(true));
+    page.insert("release".to_string(),
+                Value::String(String::from("all-time")));
+    page.insert("count".to_string(), Value::Number((scores.len() as u64).into()));
+    page.insert("scores".to_string(), Value::Array(scores));
+
+    page
+}
+
+/// describes a leaderboard's window of time, unless it covers all of time
+fn insert_range(page: &mut Page, range: &DateRange) {
+    if !range.is_all() {
+        page.insert("range".to_string(), Value::String(range.description()));
+    }
+}
+
+/// returns `None` if the author doesn't exist or has opted out
+pub fn author(conn: &PgConnection, project: &Project, author_id: i32) -> Option<Page> {
+    let mut page = base(conn, Some(project));
+
+    ::authors::profile(conn, project, author_id).map(|profile| {
+        let profile = serde_json::to_value(&profile).expect("Error serializing profile");
+        page.insert("profile".to_string(), profile);
+
+        page
+    })
+}
+
+/// returns `None` if either release doesn't exist
+pub fn range(conn: &PgConnection, project: &Project, from: &str, to: &str, range: &DateRange) -> Option<Page> {
+    let mut page = base(conn, Some(project));
+
+    insert_range(&mut page, range);
+    page.insert("from".to_string(), Value::String(from.to_string()));
+    page.insert("to".to_string(), Value::String(to.to_string()));
+
+    ::releases::range_contributors(conn, project, from, to, range).map(|contributors| {
+        let count = contributors.len() as u64;
+        let contributors = serde_json::to_value(&contributors).expect("Error serializing contributors");
+
+        page.insert("count".to_string(), Value::Number(count.into()));
+        page.insert("contributors".to_string(), contributors);
+
+        page
+    })
+}
+
+/// returns `None` if the release doesn't exist
+pub fn release(conn: &PgConnection, project: &Project, release_name: &str, range: &DateRange) -> Option<Page> {
+    let mut page = base(conn, Some(project));
+
+    insert_range(&mut page, range);
+    page.insert("release".to_string(), Value::String(release_name.to_string()));
+
+    ::releases::contributors(conn, project, release_name, range).map(|names| {
+        let new_count = names.iter()
+            .filter(|name| name["first_time"] == Value::Bool(true))
+            .count();
+
+        page.insert("count".to_string(), Value::Number((names.len() as u64).into()));
+        page.insert("new_count".to_string(), Value::Number((new_count as u64).into()));
+        page.insert("names".to_string(), Value::Array(names));
+
+        page
+    })
+}
+
+/// the Atom feed of a project's releases
+pub fn feed(conn: &PgConnection, project: &Project) -> Page {
+    let mut page = base(conn, Some(project));
+
+    let entries: Vec<Value> = ::releases::published(conn, project, FEED_LENGTH).into_iter().map(|release| {
+        let names = ::releases::contributor_names(conn, project, &release.version)
+            .unwrap_or_default();
+
+        let mut entry: Map<String, Value> = Map::new();
+
+        entry.insert("version".to_string(), Value::String(release.version));
+        entry.insert("published".to_string(),
+                     Value::String(atom_date(release.published_at.as_ref().unwrap())));
+        entry.insert("count".to_string(), Value::Number((names.len() as u64).into()));
+        entry.insert("names".to_string(), Value::Array(names.into_iter().map(Value::String).collect()));
+
+        Value::Object(entry)
+    }).collect();
+
+    // the feed was last updated when its newest release was published
+    let updated = entries.first()
+        .map(|entry| entry["published"].clone())
+        .unwrap_or_else(|| Value::String(atom_date(&UTC::now().naive_utc())));
+
+    page.insert("base_url".to_string(), Value::String(base_url()));
+    page.insert("updated".to_string(), updated);
+    page.insert("entries".to_string(), Value::Array(entries));
+
+    page
+}
+
+/// where the site is hosted, for the absolute links in the feeds
+fn base_url() -> String {
+    env::var("BASE_URL")
+        .map(|url| url.trim_right_matches('/').to_string())
+        .unwrap_or_else(|_| String::from("https://thanks.rust-lang.org"))
+}
+
+/// formats a timestamp the way Atom wants it
+fn atom_date(date: &NaiveDateTime) -> String {
+    date.format("%Y-%m-%dT%H:%M:%SZ").to_string()
+}
@@ -1,20 +1,102 @@
-use models::{NewProject, Project};
+use models::{Bot, NewProject, Project};
 
 use diesel;
 use diesel::pg::PgConnection;
 use diesel::prelude::*;
 
-pub fn create(conn: &PgConnection, name: &str, url_path: &str, github_name: &str) -> Project {
+// needed for case-insensitivity
+use diesel::types::VarChar;
+sql_function!(lower, lower_t, (x: VarChar) -> VarChar);
+
+/// creates a project named after the GitHub repository `github_name`
+///
+/// the repository itself is added with `repositories::create`
+pub fn create(conn: &PgConnection, name: &str, url_path: &str, github_name: &str) -> QueryResult<Project> {
     use schema::projects;
 
     let new_project = NewProject {
         name: name,
         url_path: url_path,
-        github_name: github_name
+        github_name: github_name,
     };
 
     diesel::insert(&new_project).into(projects::table)
         .get_result(conn)
-        .expect("Error saving new project")
 }
 
+pub fn update(conn: &PgConnection, project: &Project, new_url_path: &str, new_github_name: &str) -> QueryResult<()> {
+    use schema::projects::dsl::*;
+
+    diesel::update(projects.find(project.id))
+        .set((url_path.eq(new_url_path), github_name.eq(new_github_name)))
+        .execute(conn)
+        .map(|_| ())
+}
+
+/// returns every project, ordered by name
+pub fn all(conn: &PgConnection) -> Vec<Project> {
+    use schema::projects::dsl::*;
+
+    projects.order(name.asc())
+        .load(conn)
+        .expect("Error loading projects")
+}
+
+pub fn find(conn: &PgConnection, project_id: i32) -> Project {
+    use schema::projects::dsl::*;
+
+    projects.find(project_id)
+        .first(conn)
+        .expect("Error loading project")
+}
+
+pub fn find_by_name(conn: &PgConnection, project_name: &str) -> Option<Project> {
+    use schema::projects::dsl::*;
+
+    projects.filter(name.eq(project_name))
+        .first(conn)
+        .optional()
+        .expect("Error loading project")
+}
+
+/// finds the project served under `path`, ignoring case
+pub fn find_by_url_path(conn: &PgConnection, path: &str) -> Option<Project> {
+    use schema::projects::dsl::*;
+
+    projects.filter(lower(url_path).eq(lower(path)))
+        .first(conn)
+        .optional()
+        .expect("Error loading project")
+}
+
+/// the emails of the bots whose commits aren't credited in a project
+pub fn bots(conn: &PgConnection, project: &Project) -> Vec<String> {
+    use schema::bots::dsl::*;
+
+    bots.filter(project_id.eq(project.id))
+        .select(email)
+        .order(email.asc())
+        .load(conn)
+        .expect("Error loading bots")
+}
+
+pub fn add_bot(conn: &PgConnection, project: &Project, bot_email: &str) -> QueryResult<()> {
+    use schema::bots;
+
+    let bot = Bot {
+        project_id: project.id,
+        email: bot_email.to_string(),
+    };
+
+    diesel::insert(&bot).into(bots::table)
+        .execute(conn)
+        .map(|_| ())
+}
+
+pub fn remove_bot(conn: &PgConnection, project: &Project, bot_email: &str) -> QueryResult<()> {
+    use schema::bots::dsl::*;
+
+    diesel::delete(bots.filter(project_id.eq(project.id)).filter(email.eq(bot_email)))
+        .execute(conn)
+        .map(|_| ())
+}
@@ -1,3 +1,6 @@
+use dates::DateRange;
+use git::LogEntry;
+use mailmap::Mailmap;
 use models::*;
 use schema::*;
 
@@ -6,131 +9,376 @@ use caseless;
 use diesel::*;
 use diesel::pg::PgConnection;
 
+use serde_json::Map;
 use serde_json::value::Value;
 
 use semver::Version;
 
 use std::cmp::Ordering;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::error::Error;
 use std::io::prelude::*;
 use std::io::stderr;
-use std::process::Command;
-use std::str;
 
 use slog::Logger;
 
 use unicode_normalization::UnicodeNormalization;
 
-// needed for case-insensitivity
-use diesel::types::VarChar;
-sql_function!(lower, lower_t, (x: VarChar) -> VarChar);
-
 impl Release {
     /// provide a semver-compatible version
-    ///
-    /// rust's older versions were missing a minor version and so are not semver-compatible
     fn semver_version(&self) -> Version {
-        Version::parse(&self.version).unwrap_or_else(|_| {
-            let v = format!("{}.0", self.version);
-            Version::parse(&v).unwrap()
-        })
+        parse_version(&self.version).unwrap()
     }
 }
 
-pub fn assign_commits(log: &Logger, release_name: &str, previous_release: &str, release_project_id: i32, path: &str) {
-    use diesel::expression::dsl::any;
+/// parses a version, or returns `None` if it isn't one
+///
+/// rust's older versions were missing a minor version and so are not semver-compatible
+fn parse_version(version: &str) -> Option<Version> {
+    Version::parse(version).ok().or_else(|| {
+        Version::parse(&format!("{}.0", version)).ok()
+    })
+}
+
+/// a release found among a repository's tags
+#[derive(Debug, Clone, PartialEq)]
+pub struct TaggedRelease {
+    pub version: String,
+}
+
+/// finds the releases among a repository's tags, oldest first, followed by
+/// master
+///
+/// tags that don't match the repository's tag pattern, or don't name a version,
+/// are skipped.
+pub fn from_tags(repository: &Repository, tags: &[String]) -> Vec<TaggedRelease> {
+    let mut versions: Vec<(Version, &str)> = tags.iter()
+        .filter_map(|tag| repository.version_from_tag(tag))
+        .filter_map(|version| parse_version(version).map(|semver| (semver, version)))
+        .collect();
+    versions.sort();
+    versions.dedup_by(|a, b| a.0 == b.0);
+
+    let mut releases: Vec<TaggedRelease> = versions.into_iter()
+        .map(|(_, version)| TaggedRelease { version: version.to_string() })
+        .collect();
+
+    releases.push(TaggedRelease {
+        version: String::from("master"),
+    });
+
+    releases
+}
+
+/// assigns a release every commit in a repository that is reachable from
+/// its tag, but not from the tag of any earlier release
+///
+/// if the release has a `previous_version`, only the commits reachable from
+/// that release, or the newest earlier one tagged in the repository, are left
+/// out instead. commits we haven't seen yet are created.
+/// releases without a tag in the repository are skipped, since a repository
+/// can join a project long after its first release.
+pub fn assign_commits(conn: &PgConnection, log: &Logger, release_name: &str, repository: &Repository, path: &str,
+                      mailmap: &Mailmap) -> Result<(), Box<Error>> {
     use diesel::pg::upsert::*;
 
-    // Could take the connection as a parameter, as problably
-    // it's already established somewhere...
-    let connection = ::establish_connection();
-
-    info!(log, "Assigning commits to release {}", release_name);
-
-    let git_log = Command::new("git")
-        .arg("-C")
-        .arg(&path)
-        .arg("--no-pager")
-        .arg("log")
-        .arg("--use-mailmap")
-        .arg(r#"--format=%H %ae %an"#)
-        .arg(&format!("{}...{}", previous_release, release_name))
-        .output()
-        .expect("failed to execute process");
-
-    if !git_log.status.success() {
-        let stdout = str::from_utf8(&git_log.stdout).unwrap();
-        let stderr = str::from_utf8(&git_log.stderr).unwrap();
-        panic!(
-            "git log failed:\n\nstdout:\n{}\n\nstderr:\n{}",
-            stdout,
-            stderr,
-        );
-    }
+    info!(log, "Assigning commits in {} to release {}", repository.github_name, release_name);
 
-    let the_release = releases::table
-        .filter(releases::version.eq(&release_name))
-        .filter(releases::project_id.eq(release_project_id))
-        .first::<Release>(&connection)
-        .expect("could not find release");
-
-    let commits = str::from_utf8(&git_log.stdout).unwrap()
-        .split('\n')
-        .filter(|s| !s.is_empty())
-        .map(|line| {
-            let mut parts = line.splitn(3, ' ');
-            let sha_name = parts.next().unwrap();
-            let author_email = parts.next().unwrap();
-            let author_name = parts.next().unwrap();
-            (sha_name, author_email, author_name)
-        })
-        .collect::<Vec<_>>();
+    let mut project_releases = releases::table
+        .filter(releases::project_id.eq(repository.project_id))
+        .load::<Release>(conn)?;
+    sort(&mut project_releases);
+
+    let position = project_releases.iter()
+        .position(|r| r.version == release_name)
+        .ok_or_else(|| format!("Could not find release {}", release_name))?;
+    let the_release = &project_releases[position];
+
+    let refs = ::repositories::refs(conn, repository, &project_releases)?;
+    let tags: HashSet<String> = ::git::tags(path).into_iter().collect();
+    let tagged = |release: &Release| -> Option<String> {
+        let git_ref = &refs[&release.id];
+        if release.version == "master" || tags.contains(git_ref) {
+            Some(git_ref.clone())
+        } else {
+            None
+        }
+    };
+
+    let release_ref = match tagged(the_release) {
+        Some(release_ref) => release_ref,
+        None => {
+            writeln!(
+                stderr(),
+                "{} has no tag {} for release {}. Skipping.",
+                repository.github_name, refs[&the_release.id], release_name
+            ).unwrap();
+            return Ok(());
+        }
+    };
+
+    // a repository that joined the project later may not have a tag for the
+    // previous release, so it follows the newest one before that it does have
+    let previous_position = the_release.previous_version.as_ref()
+        .and_then(|previous| project_releases.iter().position(|r| &r.version == previous));
+    let excluded: Vec<String> = match previous_position {
+        Some(previous_position) => {
+            project_releases[..previous_position + 1].iter().rev()
+                .filter_map(|r| tagged(r))
+                .take(1)
+                .collect()
+        }
+        None => project_releases[..position].iter().filter_map(|r| tagged(r)).collect(),
+    };
 
-    if commits.is_empty() {
+    let mut revisions = vec![release_ref.as_str(), "--not"];
+    revisions.extend(excluded.iter().map(|r| r.as_str()));
+
+    let git_log = ::git::log(path, &revisions, mailmap);
+
+    let entries = git_log.iter()
+        .map(|entry| (entry.sha.as_str(), entry))
+        .collect::<HashMap<_, _>>();
+
+    if git_log.is_empty() {
         writeln!(
             stderr(),
-            "Could not find commits between {} and {} (maybe the tag is \
-            missing?) Skipping.",
-            previous_release,
+            "Could not find any commits in {} (maybe the tag is missing?) \
+            Skipping.",
             release_name
         ).unwrap();
         // https://github.com/diesel-rs/diesel/issues/797
-        return;
+        return Ok(());
     }
 
-    connection.transaction::<_, Box<Error>, _>(|| {
-        let (shas, commits): (Vec<_>, Vec<_>) =
-            authors_by_sha(&connection, commits)?
+    conn.transaction::<_, Box<Error>, _>(|| {
+        // commits we already have only move to this release, so their
+        // authors don't need to be looked up again
+        let existing = ::commits::existing(conn, entries.keys().cloned().collect())?;
+        let (moved, new): (Vec<&LogEntry>, Vec<&LogEntry>) = git_log.iter()
+            .partition(|entry| existing.contains(&entry.sha));
+
+        let updated = ::commits::reassign(conn, the_release, &moved)?;
+
+        let inserted = if new.is_empty() {
+            // https://github.com/diesel-rs/diesel/issues/797
+            0
+        } else {
+            let new_commits: Vec<NewCommit> = authors_by_sha(conn, new.iter().map(|entry| {
+                (entry.sha.as_str(), entry.author_email.as_str(), entry.author_name.as_str())
+            }).collect())?
                 .into_iter()
                 .map(|(sha, author_id)| {
-                    (sha, NewCommit {
+                    NewCommit {
                         sha: sha,
                         release_id: the_release.id,
                         author_id: author_id,
-                    })
+                        authored_at: Some(entries[sha].authored_at),
+                        committed_at: Some(entries[sha].committed_at),
+                    }
                 })
-                .unzip();
+                .collect();
 
-        // Set the release id of any commits that already existed
-        // FIXME: In Diesel 0.12 collapse this with the next line to use
-        // .on_conflict(sha, do_update().set(commits::release_id.eq(the_release.id)))
-        let updated = update(commits::table.filter(commits::sha.eq(any(shas))))
-            .set(commits::release_id.eq(the_release.id))
-            .execute(&connection)?;
+            insert(&new_commits.on_conflict_do_nothing())
+                .into(commits::table)
+                .execute(conn)?
+        };
 
-        let inserted = insert(&commits.on_conflict_do_nothing())
-            .into(commits::table)
-            .execute(&connection)?;
+        let co_authors = git_log.iter()
+            .flat_map(|entry| entry.co_authors.iter().map(move |co_author| (entry.sha.as_str(), co_author)))
+            .collect();
+        ::commits::add_co_authors(conn, co_authors)?;
 
         let total = updated + inserted;
-        if total == commits.len() {
+        if total == git_log.len() {
             Ok(())
         } else {
             Err(format!("Expected to create or update {} commits, \
-                         but only {} were", commits.len(), total).into())
+                         but only {} were", git_log.len(), total).into())
+        }
+    })?;
+
+    // master is where backports come from, so it can't contain any
+    if release_name != "master" {
+        let backports = find_backports(repository, path, &release_ref, &git_log);
+        info!(log, "Found {} backported commits in release {}", backports.len(), release_name);
+
+        ::commits::mark_backports(conn, entries.keys().cloned().collect(), backports)?;
+    }
+
+    mark_assigned(conn, repository, the_release)?;
+
+    Ok(())
+}
+
+fn mark_assigned(conn: &PgConnection, repository: &Repository, release: &Release) -> QueryResult<()> {
+    use diesel::pg::upsert::*;
+
+    let assigned = AssignedRelease {
+        repository_id: repository.id,
+        release_id: release.id,
+    };
+
+    insert(&assigned.on_conflict_do_nothing())
+        .into(assigned_releases::table)
+        .execute(conn)
+        .map(|_| ())
+}
+
+/// whether a release's commits were assigned from a repository before
+pub fn is_assigned(conn: &PgConnection, repository: &Repository, release: &Release) -> QueryResult<bool> {
+    assigned_releases::table
+        .filter(assigned_releases::repository_id.eq(repository.id))
+        .filter(assigned_releases::release_id.eq(release.id))
+        .count()
+        .get_result::<i64>(conn)
+        .map(|count| count > 0)
+}
+
+/// finds the commits in a release that were cherry-picked from master, as
+/// (backport, original) pairs
+///
+/// backports are found by the line `git cherry-pick -x` adds, or failing
+/// that, by having the same patch id as a commit on master
+fn find_backports(repository: &Repository, path: &str, release_ref: &str, git_log: &[LogEntry]) -> Vec<(String, String)> {
+    let master = repository.git_ref("master");
+
+    // only commits that master doesn't have can be backports
+    let unmerged: HashSet<String> = ::git::rev_list(path, &[release_ref, "--not", &master])
+        .into_iter()
+        .collect();
+    let candidates: Vec<&LogEntry> = git_log.iter()
+        .filter(|entry| unmerged.contains(&entry.sha))
+        .collect();
+
+    let mut backports: Vec<(String, String)> = candidates.iter()
+        .filter_map(|entry| entry.cherry_picked_from.clone().map(|original| (entry.sha.clone(), original)))
+        .collect();
+
+    let unmarked: Vec<&&LogEntry> = candidates.iter()
+        .filter(|entry| entry.cherry_picked_from.is_none())
+        .collect();
+    if unmarked.is_empty() {
+        return backports;
+    }
+
+    // a commit can't be cherry-picked before it was made, so the originals
+    // are on master after the release branched off, and before the last
+    // backport was committed
+    let until = unmarked.iter().map(|entry| entry.committed_at).max().unwrap();
+    let until = format!("--until={}", until.format("%Y-%m-%d %H:%M:%S +0000"));
+
+    let originals: HashMap<String, String> = ::git::patch_ids(path, &[&master, "--not", release_ref, &until])
+        .into_iter()
+        .map(|(sha, patch_id)| (patch_id, sha))
+        .collect();
+
+    let unmarked: HashSet<&str> = unmarked.iter().map(|entry| entry.sha.as_str()).collect();
+    for (sha, patch_id) in ::git::patch_ids(path, &[release_ref, "--not", &master]) {
+        if !unmarked.contains(sha.as_str()) {
+            continue;
         }
-    }).expect("Error saving commits and authors");
+
+        if let Some(original) = originals.get(&patch_id) {
+            backports.push((sha, original.clone()));
+        }
+    }
+
+    backports
+}
+
+/// a commit that moved to another release
+#[derive(Debug, Serialize)]
+pub struct Reassignment {
+    pub sha: String,
+    pub from: String,
+    pub to: String,
+}
+
+/// assigns every commit in one of a project's repositories to its release
+/// again, oldest release first, and reports the commits that ended up in a
+/// different release
+///
+/// this is how to fix the releases after a `previous_version` or a tag
+/// changes, or after commits were assigned with older rules
+pub fn recompute(conn: &PgConnection, log: &Logger, repository: &Repository, path: &str, mailmap: &Mailmap)
+    -> Result<Vec<Reassignment>, Box<Error>>
+{
+    let mut project_releases = releases::table
+        .filter(releases::project_id.eq(repository.project_id))
+        .load::<Release>(conn)?;
+    sort(&mut project_releases);
+
+    let before = release_ids_by_sha(conn, &project_releases)?;
+
+    for release in project_releases.iter() {
+        assign_commits(conn, log, &release.version, repository, path, mailmap)?;
+    }
+
+    let after = release_ids_by_sha(conn, &project_releases)?;
+
+    let versions: HashMap<i32, &str> = project_releases.iter()
+        .map(|r| (r.id, r.version.as_str()))
+        .collect();
+
+    let mut reassignments: Vec<Reassignment> = after.into_iter()
+        .filter_map(|(sha, to)| match before.get(&sha) {
+            Some(&from) if from != to => Some(Reassignment {
+                sha: sha,
+                from: versions[&from].to_string(),
+                to: versions[&to].to_string(),
+            }),
+            _ => None,
+        })
+        .collect();
+    reassignments.sort_by(|a, b| a.sha.cmp(&b.sha));
+
+    Ok(reassignments)
+}
+
+fn release_ids_by_sha(conn: &PgConnection, project_releases: &[Release]) -> QueryResult<HashMap<String, i32>> {
+    use diesel::expression::dsl::any;
+
+    let ids: Vec<i32> = project_releases.iter().map(|r| r.id).collect();
+
+    commits::table
+        .filter(commits::release_id.eq(any(ids)))
+        .select((commits::sha, commits::release_id))
+        .load::<(String, i32)>(conn)
+        .map(|rows| rows.into_iter().collect())
+}
+
+/// overrides which release a release follows, or goes back to following
+/// every earlier release if `previous_version` is `None`
+///
+/// the commits aren't moved until they're assigned again
+pub fn set_previous(conn: &PgConnection, release: &Release, previous_version: Option<&str>) -> QueryResult<()> {
+    update(releases::table.find(release.id))
+        .set(releases::previous_version.eq(previous_version))
+        .execute(conn)
+        .map(|_| ())
+}
+
+/// shows or hides a release, publishing it the first time it's shown
+pub fn set_visible(conn: &PgConnection, release: &Release, visible: bool) -> QueryResult<()> {
+    update(releases::table.find(release.id))
+        .set(releases::visible.eq(visible))
+        .execute(conn)?;
+
+    if visible {
+        publish(conn, release)?;
+    }
+
+    Ok(())
+}
+
+/// finds a release of a project by its version
+pub fn find(conn: &PgConnection, project: &Project, version: &str) -> Option<Release> {
+    Release::belonging_to(project)
+        .filter(releases::version.eq(version))
+        .first(conn)
+        .optional()
+        .expect("Error loading release")
 }
 
 type Sha<'a> = &'a str;
@@ -155,7 +403,7 @@ fn authors_by_sha<'a>(conn: &PgConnection, git_log: Vec<(Sha<'a>, Email, Name)>)
         .collect())
 }
 
-pub fn create(conn: &PgConnection, version: &str, project_id: i32, visible: bool) -> Release {
+pub fn create(conn: &PgConnection, version: &str, project_id: i32, visible: bool) -> QueryResult<Release> {
     use schema::releases;
 
     let new_release = NewRelease {
@@ -166,48 +414,191 @@ pub fn create(conn: &PgConnection, version: &str, project_id: i32, visible: bool
 
     insert(&new_release).into(releases::table)
         .get_result(conn)
-        .expect("Error saving new release")
 }
 
-pub fn contributors(project: &str, release_name: &str) -> Option<Vec<Value>> {
-    use schema::releases::dsl::*;
-    use schema::commits::dsl::*;
-    use models::Release;
+/// the release that a project's unreleased commits belong to
+pub fn master(conn: &PgConnection, project: &Project) -> QueryResult<Release> {
+    releases::table
+        .filter(releases::project_id.eq(project.id))
+        .filter(releases::version.eq("master"))
+        .first(conn)
+}
 
-    let connection = ::establish_connection();
+/// records that a release was announced, so that it shows up in the feed
+///
+/// releases that were already published keep their original date
+pub fn publish(conn: &PgConnection, release: &Release) -> QueryResult<()> {
+    use chrono::UTC;
+
+    update(releases::table.find(release.id).filter(releases::published_at.is_null()))
+        .set(releases::published_at.eq(Some(UTC::now().naive_utc())))
+        .execute(conn)
+        .map(|_| ())
+}
 
-    let project = {
-        use schema::projects::dsl::*;
+/// the most recently published releases of a project, newest first
+pub fn published(conn: &PgConnection, project: &Project, limit: i64) -> Vec<Release> {
+    releases::table
+        .filter(releases::project_id.eq(project.id))
+        .filter(releases::visible.eq(true))
+        .filter(releases::published_at.is_not_null())
+        .order(releases::published_at.desc())
+        .limit(limit)
+        .load(conn)
+        .expect("Error loading published releases")
+}
 
-        match projects.filter(lower(name).eq(lower(project)))
-            .first::<Project>(&connection) {
-                Ok(p) => p,
-                Err(_) => {
-                    return None;
-                }
-        }
+/// someone who contributed to a release
+#[derive(Debug, Serialize)]
+pub struct Contributor {
+    pub name: String,
+    /// this release contains their earliest commit to the project
+    pub first_time: bool,
+}
+
+pub fn contributors(conn: &PgConnection, project: &Project, release_name: &str, range: &DateRange)
+    -> Option<Vec<Value>>
+{
+    contributor_list(conn, project, release_name, range).map(|contributors| {
+        contributors.into_iter().map(|contributor| {
+            let mut json_contributor: Map<String, Value> = Map::new();
+
+            json_contributor.insert("name".to_string(), Value::String(contributor.name));
+            json_contributor.insert("first_time".to_string(), Value::Bool(contributor.first_time));
+
+            Value::Object(json_contributor)
+        }).collect()
+    })
+}
+
+/// the sorted names of everyone who contributed to a release
+///
+/// returns `None` if the release doesn't exist
+pub fn contributor_names(conn: &PgConnection, project: &Project, release_name: &str) -> Option<Vec<String>> {
+    contributor_list(conn, project, release_name, &DateRange::all())
+        .map(|contributors| contributors.into_iter().map(|c| c.name).collect())
+}
+
+/// everyone who contributed to a release within `range`, sorted by name
+///
+/// whether they contributed for the first time doesn't depend on `range`.
+/// returns `None` if the release doesn't exist
+pub fn contributor_list(conn: &PgConnection, project: &Project, release_name: &str, range: &DateRange)
+    -> Option<Vec<Contributor>>
+{
+    use schema::contributions::dsl::*;
+    use diesel::expression::dsl::{any, sql};
+    use diesel::types::Bool;
+    use std::collections::HashSet;
+
+    let mut project_releases = releases::table
+        .filter(releases::project_id.eq(project.id))
+        .load::<Release>(conn)
+        .expect("Error loading releases");
+    sort(&mut project_releases);
+
+    let position = match project_releases.iter().position(|r| r.version == release_name) {
+        Some(position) => position,
+        None => return None,
     };
 
-    let release: Release = match releases
-        .filter(version.eq(release_name))
-        .filter(project_id.eq(project.id))
-        .first(&connection) {
-            Ok(release) => release,
-                Err(_) => {
-                    return None;
-                },
-        };
+    // anyone who has a commit in an earlier release isn't new
+    let earlier_releases: Vec<i32> = project_releases[..position].iter()
+        .map(|r| r.id)
+        .collect();
+    let previous_authors: HashSet<i32> = contributions
+        .filter(release_id.eq(any(earlier_releases)))
+        .select(author_id)
+        .distinct()
+        .load(conn)
+        .expect("Error loading previous authors")
+        .into_iter()
+        .collect();
+
+    let release = &project_releases[position];
+    let release_authors: Vec<(i32, String)> = authors::table.inner_join(contributions)
+        .filter(release_id.eq(release.id))
+        .filter(authors::visible.eq(true))
+        .filter(sql::<Bool>(&range.sql_filter()))
+        .select((authors::id, authors::name))
+        .distinct()
+        .load(conn)
+        .unwrap();
+
+    // several authors can share a name; they're only new if all of them are
+    let mut first_time: HashMap<String, bool> = HashMap::new();
+    for (author, author_name) in release_authors {
+        *first_time.entry(author_name).or_insert(true) &= !previous_authors.contains(&author);
+    }
+
+    let mut contributors: Vec<Contributor> = first_time.into_iter()
+        .map(|(author_name, is_new)| Contributor { name: author_name, first_time: is_new })
+        .collect();
 
     // it'd be better to do this in the db
     // but Postgres doesn't do Unicode collation correctly on OSX
     // http://postgresql.nabble.com/Collate-order-on-Mac-OS-X-text-with-diacritics-in-UTF-8-td1912473.html
-    use schema::authors;
-    let mut names: Vec<String> = authors::table.inner_join(commits).filter(release_id.eq(release.id))
-        .filter(authors::visible.eq(true)).select(authors::name).distinct().load(&connection).unwrap();
+    contributors.sort_by(|a, b| str_cmp(&a.name, &b.name));
 
-    inaccurate_sort(&mut names);
+    Some(contributors)
+}
+
+/// someone who contributed to a range of releases
+#[derive(Debug, Serialize)]
+pub struct RangeContributor {
+    pub name: String,
+    pub commits: i64,
+}
+
+/// everyone who contributed to any release from `from` to `to`, inclusive,
+/// within `range`
+///
+/// the releases may be given in either order. returns `None` if either of
+/// them doesn't exist.
+pub fn range_contributors(conn: &PgConnection, project: &Project, from: &str, to: &str, range: &DateRange)
+    -> Option<Vec<RangeContributor>>
+{
+    use schema::contributions::dsl::*;
+    use diesel::expression::dsl::{any, sql};
+    use diesel::types::{BigInt, Bool};
+
+    let mut project_releases = releases::table
+        .filter(releases::project_id.eq(project.id))
+        .load::<Release>(conn)
+        .expect("Error loading releases");
+    sort(&mut project_releases);
+
+    let start = project_releases.iter().position(|r| r.version == from);
+    let end = project_releases.iter().position(|r| r.version == to);
+    let (start, end) = match (start, end) {
+        (Some(start), Some(end)) if start <= end => (start, end),
+        (Some(start), Some(end)) => (end, start),
+        _ => return None,
+    };
 
-    Some(names.into_iter().map(Value::String).collect())
+    let release_ids: Vec<i32> = project_releases[start..end + 1].iter()
+        .map(|r| r.id)
+        .collect();
+
+    // grouping by name merges authors who used several emails
+    let counts: Vec<(String, i64)> = contributions.inner_join(authors::table)
+        .filter(release_id.eq(any(release_ids)))
+        .filter(sql::<Bool>(&range.sql_filter()))
+        .filter(authors::visible.eq(true))
+        // backports were already counted when they landed on master
+        .filter(backport_of.is_null())
+        .select((authors::name, sql::<BigInt>("COUNT(*)")))
+        .group_by(authors::name)
+        .load(conn)
+        .expect("Error counting commits");
+
+    let mut contributors: Vec<RangeContributor> = counts.into_iter()
+        .map(|(author_name, count)| RangeContributor { name: author_name, commits: count })
+        .collect();
+
+    contributors.sort_by(|a, b| str_cmp(&a.name, &b.name));
+
+    Some(contributors)
 }
 
 // TODO: switch this out for an implementation of the Unicode Collation Algorithm
@@ -257,47 +648,49 @@ fn char_cmp(a_char: char, b_char: char) -> Ordering {
     order
 }
 
-/// returns all releases
+/// returns all releases of a project
 ///
 /// sorted in semver order
-pub fn all() -> Vec<Value> {
+pub fn all(conn: &PgConnection, project: &Project) -> Vec<Value> {
+    versions(conn, project).into_iter().map(Value::String).collect()
+}
+
+/// returns the version of every visible release of a project
+///
+/// master comes first, followed by the rest in descending semver order
+pub fn versions(conn: &PgConnection, project: &Project) -> Vec<String> {
     use schema::releases::dsl::*;
     use models::Release;
-    use models::Project;
-
-    let connection = ::establish_connection();
-
-    let project = {
-        use schema::projects::dsl::*;
-        projects.filter(name.eq("Rust"))
-            .first::<Project>(&connection)
-        .expect("Error finding the Rust project")
-    };
 
     let mut results = releases.filter(project_id.eq(project.id))
         .filter(visible.eq(true))
-        .load::<Release>(&connection)
+        .load::<Release>(conn)
         .expect("Error loading releases");
 
-    // sort the versions
-    //
-    // first we need to remove master as it is not a valid semver version, and
-    // master should be at the top anyway
-    let master = match results.iter().position(|r| r.version == "master") {
-        Some(i) => results.remove(i),
-        None => panic!("master release not found"),
-    };
+    sort(&mut results);
+
+    results.into_iter()
+        .rev()
+        .map(|r| r.version)
+        .collect()
+}
+
+/// sorts releases from oldest to newest
+///
+/// master isn't a valid semver version, but it always contains the newest
+/// commits, so it goes last.
+pub fn sort(results: &mut Vec<Release>) {
+    // first we need to remove master. projects that were just created
+    // might not have one yet.
+    let master = results.iter()
+        .position(|r| r.version == "master")
+        .map(|i| results.remove(i));
 
     // next up, sort by semver version
     results.sort_by(|a, b| {
         a.semver_version().cmp(&b.semver_version())
     });
 
-    // finally, push master/all-time back at the top
-    results.push(master);
-
-    results.into_iter()
-        .rev()
-        .map(|r| Value::String(r.version))
-        .collect()
+    // finally, push master back at the end
+    results.extend(master);
 }
@@ -0,0 +1,238 @@
+//! The repositories a project's releases are made of.
+//!
+//! Most projects are a single repository, but a release of Rust also ships
+//! Cargo, the book, and so on. Every repository names its tags its own way,
+//! so each one has a tag pattern, and tags that don't fit it at all can be
+//! set for single releases.
+
+use models::{NewRepository, Project, Release, Repository, RepositoryTag};
+use projects::lower;
+
+use chrono::{NaiveDateTime, UTC};
+
+use diesel;
+use diesel::pg::PgConnection;
+use diesel::prelude::*;
+
+use std::collections::HashMap;
+
+impl Repository {
+    /// the git ref a release can be found at, following the tag pattern
+    ///
+    /// master is the repository's default branch rather than a tag
+    pub fn git_ref(&self, version: &str) -> String {
+        if version == "master" {
+            return self.default_branch.clone();
+        }
+
+        self.tag_pattern.replace("{version}", version)
+    }
+
+    /// the version a tag names, if it matches this repository's tag pattern
+    pub fn version_from_tag<'a>(&self, tag: &'a str) -> Option<&'a str> {
+        let mut parts = self.tag_pattern.splitn(2, "{version}");
+        let prefix = parts.next().unwrap();
+        let suffix = parts.next().expect("tag patterns must contain {version}");
+
+        if tag.len() > prefix.len() + suffix.len() && tag.starts_with(prefix) && tag.ends_with(suffix) {
+            Some(&tag[prefix.len()..tag.len() - suffix.len()])
+        } else {
+            None
+        }
+    }
+}
+
+pub fn create(conn: &PgConnection, project: &Project, github_name: &str, tag_pattern: &str,
+              mailmap: Option<&str>, default_branch: &str) -> QueryResult<Repository> {
+    use schema::repositories;
+
+    let new_repository = NewRepository {
+        project_id: project.id,
+        github_name: github_name,
+        tag_pattern: tag_pattern,
+        mailmap: mailmap,
+        default_branch: default_branch,
+    };
+
+    diesel::insert(&new_repository).into(repositories::table)
+        .get_result(conn)
+}
+
+pub fn update(conn: &PgConnection, repository: &Repository, new_tag_pattern: &str, new_mailmap: Option<&str>,
+              new_default_branch: &str) -> QueryResult<()> {
+    use schema::repositories::dsl::*;
+
+    diesel::update(repositories.find(repository.id))
+        .set((tag_pattern.eq(new_tag_pattern), mailmap.eq(new_mailmap), default_branch.eq(new_default_branch)))
+        .execute(conn)
+        .map(|_| ())
+}
+
+/// every repository of a project, in the order they were added
+pub fn all(conn: &PgConnection, project: &Project) -> Vec<Repository> {
+    use schema::repositories::dsl::*;
+
+    Repository::belonging_to(project)
+        .order(id.asc())
+        .load(conn)
+        .expect("Error loading repositories")
+}
+
+/// finds a repository by its GitHub name, like `rust-lang/rust`, ignoring
+/// case
+pub fn find_by_github_name(conn: &PgConnection, name: &str) -> Option<Repository> {
+    use schema::repositories::dsl::*;
+
+    repositories.filter(lower(github_name).eq(lower(name)))
+        .first(conn)
+        .optional()
+        .expect("Error loading repository")
+}
+
+/// every repository that accepts webhook deliveries
+pub fn with_webhook_secret(conn: &PgConnection) -> QueryResult<Vec<Repository>> {
+    use schema::repositories::dsl::*;
+
+    repositories.filter(webhook_secret.is_not_null())
+        .order(id.asc())
+        .load(conn)
+}
+
+/// sets the secret GitHub signs a repository's webhook deliveries with, or
+/// turns the webhook off if `secret` is `None`
+pub fn set_webhook_secret(conn: &PgConnection, repository: &Repository, secret: Option<&str>) -> QueryResult<()> {
+    use schema::repositories::dsl::*;
+
+    diesel::update(repositories.find(repository.id))
+        .set(webhook_secret.eq(secret))
+        .execute(conn)
+        .map(|_| ())
+}
+
+/// asks `update-commit-db --requested` to fetch a repository's commits from
+/// the API
+pub fn request_sync(conn: &PgConnection, repository: &Repository) -> QueryResult<()> {
+    use schema::repositories::dsl::*;
+
+    diesel::update(repositories.find(repository.id))
+        .set(sync_requested_at.eq(Some(UTC::now().naive_utc())))
+        .execute(conn)
+        .map(|_| ())
+}
+
+/// forgets the requests to sync a repository made before `synced_at`, when
+/// the sync that fetched their commits started
+pub fn finish_sync_requests(conn: &PgConnection, repository: &Repository, synced_at: NaiveDateTime)
+    -> QueryResult<()>
+{
+    use schema::repositories::dsl::*;
+
+    diesel::update(repositories.find(repository.id).filter(sync_requested_at.le(synced_at)))
+        .set(sync_requested_at.eq(None::<NaiveDateTime>))
+        .execute(conn)
+        .map(|_| ())
+}
+
+/// sets the tag a release has in a repository, for tags that don't follow
+/// its tag pattern
+pub fn set_tag(conn: &PgConnection, repository: &Repository, release: &Release, tag: &str) -> QueryResult<()> {
+    use schema::repository_tags::dsl::*;
+
+    let new_tag = RepositoryTag {
+        repository_id: repository.id,
+        release_id: release.id,
+        tag: tag.to_string(),
+    };
+
+    conn.transaction(|| {
+        diesel::delete(repository_tags.filter(repository_id.eq(repository.id)).filter(release_id.eq(release.id)))
+            .execute(conn)?;
+        diesel::insert(&new_tag).into(repository_tags)
+            .execute(conn)
+            .map(|_| ())
+    })
+}
+
+/// removes the tag a release has in a repository, so that it follows the
+/// tag pattern again
+pub fn remove_tag(conn: &PgConnection, repository: &Repository, release: &Release) -> QueryResult<()> {
+    use schema::repository_tags::dsl::*;
+
+    diesel::delete(repository_tags.filter(repository_id.eq(repository.id)).filter(release_id.eq(release.id)))
+        .execute(conn)
+        .map(|_| ())
+}
+
+/// the tags that were set for releases in a repository
+pub fn tags(conn: &PgConnection, repository: &Repository) -> Vec<RepositoryTag> {
+    use schema::repository_tags::dsl::*;
+
+    repository_tags.filter(repository_id.eq(repository.id))
+        .load(conn)
+        .expect("Error loading tags")
+}
+
+/// the git ref of each release in a repository, by release id
+pub fn refs(conn: &PgConnection, repository: &Repository, releases: &[Release]) -> QueryResult<HashMap<i32, String>> {
+    use schema::repository_tags::dsl::*;
+
+    let tags: HashMap<i32, String> = repository_tags
+        .filter(repository_id.eq(repository.id))
+        .select((release_id, tag))
+        .load::<(i32, String)>(conn)?
+        .into_iter()
+        .collect();
+
+    Ok(releases.iter().map(|release| {
+        let git_ref = tags.get(&release.id)
+            .cloned()
+            .unwrap_or_else(|| repository.git_ref(&release.version));
+
+        (release.id, git_ref)
+    }).collect())
+}
+
+/// pairs repositories of a project with the values given for them on the
+/// command line, like the paths of their checkouts
+///
+/// `values` look like `rust-lang/cargo=~/src/cargo`. a project with a single
+/// repository can leave out the name, like `~/src/rust`.
+pub fn by_name(conn: &PgConnection, project: &Project, values: &[&str]) -> Result<Vec<(Repository, String)>, String> {
+    let repositories = all(conn, project);
+    let mut named = Vec::new();
+
+    for value in values {
+        let mut parts = value.splitn(2, '=');
+        let (name, value) = match (parts.next(), parts.next()) {
+            (Some(name), Some(value)) => (Some(name), value),
+            (Some(value), None) => (None, value),
+            _ => unreachable!(),
+        };
+
+        let repository = match name {
+            Some(name) => {
+                repositories.iter()
+                    .find(|r| r.github_name.to_lowercase() == name.to_lowercase())
+                    .ok_or_else(|| format!("{} has no repository {}", project.name, name))?
+            }
+            None if repositories.len() == 1 => &repositories[0],
+            None if repositories.is_empty() => return Err(format!("{} has no repositories", project.name)),
+            None => {
+                return Err(format!("{} has several repositories, so {} needs to say which it's for, \
+                                    like {}={}", project.name, value, repositories[0].github_name, value));
+            }
+        };
+
+        named.push((repository.id, value.to_string()));
+    }
+
+    Ok(repositories.into_iter()
+        .filter_map(|repository| {
+            let value = named.iter()
+                .find(|&&(id, _)| id == repository.id)
+                .map(|&(_, ref value)| value.clone());
+
+            value.map(|value| (repository, value))
+        })
+        .collect())
+}
@@ -1 +1,12 @@
 infer_schema!("dotenv:DATABASE_URL");
+
+// views aren't inferred, so they're declared by hand
+table! {
+    contributions (sha, author_id) {
+        sha -> VarChar,
+        release_id -> Integer,
+        author_id -> Integer,
+        authored_at -> Nullable<Timestamp>,
+        backport_of -> Nullable<VarChar>,
+    }
+}
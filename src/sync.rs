@@ -0,0 +1,373 @@
+//! Making the database match the config.
+//!
+//! Projects, repositories and bots are created and updated to match, and so
+//! are the settings of releases that already exist. Nothing is ever deleted
+//! except bots and tags, so projects and repositories missing from the config
+//! are only warned about.
+
+use config::{Config, ProjectConfig, RepositoryConfig};
+use models::{Project, Release, Repository};
+
+use diesel::pg::PgConnection;
+use diesel::prelude::*;
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+/// a single change to the database
+///
+/// changes serialize as an object with the variant's name as its only key
+#[derive(Debug, PartialEq, Serialize)]
+pub enum Change {
+    CreateProject { name: String, url_path: String, github_name: String },
+    /// the changed fields are given as (old, new)
+    UpdateProject { name: String, url_path: (String, String), github_name: (String, String) },
+    CreateRepository {
+        project: String,
+        github_name: String,
+        tag_pattern: String,
+        mailmap: Option<String>,
+        default_branch: String,
+    },
+    UpdateRepository {
+        github_name: String,
+        tag_pattern: (String, String),
+        mailmap: (Option<String>, Option<String>),
+        default_branch: (String, String),
+    },
+    AddBot { project: String, email: String },
+    RemoveBot { project: String, email: String },
+    /// `None` removes the tag, so the release follows the tag pattern again
+    SetTag { project: String, github_name: String, version: String, tag: Option<String> },
+    SetPrevious { project: String, version: String, previous: Option<String> },
+    SetVisible { project: String, version: String, visible: bool },
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Change::CreateProject { ref name, ref url_path, ref github_name } => {
+                write!(f, "create project {} at /{} ({})", name, url_path, github_name)
+            }
+            Change::UpdateProject { ref name, ref url_path, ref github_name } => {
+                write!(f, "update project {}:", name)?;
+                field(f, "url_path", &url_path.0, &url_path.1)?;
+                field(f, "github_name", &github_name.0, &github_name.1)
+            }
+            Change::CreateRepository { ref project, ref github_name, ref tag_pattern, ref mailmap, ref default_branch } => {
+                write!(f, "add repository {} to {} (tags {}, branch {}", github_name, project, tag_pattern, default_branch)?;
+                if let Some(ref mailmap) = *mailmap {
+                    write!(f, ", mailmap {}", mailmap)?;
+                }
+                write!(f, ")")
+            }
+            Change::UpdateRepository { ref github_name, ref tag_pattern, ref mailmap, ref default_branch } => {
+                write!(f, "update repository {}:", github_name)?;
+                field(f, "tag_pattern", &tag_pattern.0, &tag_pattern.1)?;
+                field(f, "mailmap", &or_none(&mailmap.0), &or_none(&mailmap.1))?;
+                field(f, "default_branch", &default_branch.0, &default_branch.1)
+            }
+            Change::AddBot { ref project, ref email } => write!(f, "add bot {} to {}", email, project),
+            Change::RemoveBot { ref project, ref email } => write!(f, "remove bot {} from {}", email, project),
+            Change::SetTag { ref project, ref github_name, ref version, tag: Some(ref tag) } => {
+                write!(f, "tag {} {} as {} in {}", project, version, tag, github_name)
+            }
+            Change::SetTag { ref project, ref github_name, ref version, tag: None } => {
+                write!(f, "tag {} {} with the tag pattern in {}", project, version, github_name)
+            }
+            Change::SetPrevious { ref project, ref version, previous: Some(ref previous) } => {
+                write!(f, "make {} {} follow {}", project, version, previous)
+            }
+            Change::SetPrevious { ref project, ref version, previous: None } => {
+                write!(f, "make {} {} follow every earlier release", project, version)
+            }
+            Change::SetVisible { ref project, ref version, visible } => {
+                write!(f, "{} {} {}", if visible { "show" } else { "hide" }, project, version)
+            }
+        }
+    }
+}
+
+/// writes ` name: old -> new` if the field changed
+fn field(f: &mut fmt::Formatter, name: &str, old: &str, new: &str) -> fmt::Result {
+    if old == new {
+        Ok(())
+    } else {
+        write!(f, " {}: {} -> {}", name, old, new)
+    }
+}
+
+fn or_none(value: &Option<String>) -> String {
+    value.clone().unwrap_or_else(|| String::from("(none)"))
+}
+
+/// what it takes to make the database match the config
+#[derive(Debug, Default)]
+pub struct Plan {
+    pub changes: Vec<Change>,
+    /// things that don't match, but that sync won't change
+    pub warnings: Vec<String>,
+}
+
+/// works out the changes that make the database match `config`
+pub fn plan(conn: &PgConnection, config: &Config) -> Plan {
+    let mut plan = Plan::default();
+
+    for project in ::projects::all(conn) {
+        if !config.projects.iter().any(|p| p.name == project.name) {
+            plan.warnings.push(format!("{} isn't in the config, so it's left as it is", project.name));
+        }
+    }
+
+    for project_config in config.projects.iter() {
+        match ::projects::find_by_name(conn, &project_config.name) {
+            Some(project) => plan_project(conn, &mut plan, project_config, &project),
+            None => plan_new_project(conn, &mut plan, project_config),
+        }
+    }
+
+    plan
+}
+
+fn plan_new_project(conn: &PgConnection, plan: &mut Plan, config: &ProjectConfig) {
+    plan.changes.push(Change::CreateProject {
+        name: config.name.clone(),
+        url_path: config.url_path.clone(),
+        github_name: config.github_name().to_string(),
+    });
+
+    for repository in config.repositories.iter() {
+        plan_new_repository(conn, plan, config, repository);
+    }
+
+    for email in config.bots.iter() {
+        plan.changes.push(Change::AddBot { project: config.name.clone(), email: email.clone() });
+    }
+
+    if !config.releases.is_empty() || config.repositories.iter().any(|r| !r.tags.is_empty()) {
+        plan.warnings.push(format!("{} has no releases yet; run sync again after populate to set them up",
+                                   config.name));
+    }
+}
+
+/// returns whether the repository can be added
+fn plan_new_repository(conn: &PgConnection, plan: &mut Plan, project: &ProjectConfig, config: &RepositoryConfig)
+    -> bool
+{
+    if let Some(existing) = ::repositories::find_by_github_name(conn, &config.github_name) {
+        let owner = ::projects::find(conn, existing.project_id);
+        plan.warnings.push(format!("{} already belongs to {}, so it can't be added to {}",
+                                   config.github_name, owner.name, project.name));
+        return false;
+    }
+
+    plan.changes.push(Change::CreateRepository {
+        project: project.name.clone(),
+        github_name: config.github_name.clone(),
+        tag_pattern: config.tag_pattern.clone(),
+        mailmap: config.mailmap.clone(),
+        default_branch: config.default_branch.clone(),
+    });
+
+    true
+}
+
+fn plan_project(conn: &PgConnection, plan: &mut Plan, config: &ProjectConfig, project: &Project) {
+    let github_name = config.github_name();
+    if project.url_path != config.url_path || project.github_name != github_name {
+        plan.changes.push(Change::UpdateProject {
+            name: project.name.clone(),
+            url_path: (project.url_path.clone(), config.url_path.clone()),
+            github_name: (project.github_name.clone(), github_name.to_string()),
+        });
+    }
+
+    let mut releases = Release::belonging_to(project)
+        .load::<Release>(conn)
+        .expect("Error loading releases");
+    ::releases::sort(&mut releases);
+    let versions: HashMap<i32, &str> = releases.iter().map(|r| (r.id, r.version.as_str())).collect();
+
+    let repositories = ::repositories::all(conn, project);
+    for repository in repositories.iter() {
+        if !config.repositories.iter().any(|r| same_name(&r.github_name, &repository.github_name)) {
+            plan.warnings.push(format!("{} isn't in the config, so it's left as it is", repository.github_name));
+        }
+    }
+
+    for repository_config in config.repositories.iter() {
+        let existing = repositories.iter().find(|r| same_name(&r.github_name, &repository_config.github_name));
+
+        let current_tags: HashMap<String, String> = match existing {
+            Some(repository) => {
+                plan_repository(plan, repository_config, repository);
+
+                ::repositories::tags(conn, repository).into_iter()
+                    .map(|tag| (versions[&tag.release_id].to_string(), tag.tag))
+                    .collect()
+            }
+            None => {
+                if !plan_new_repository(conn, plan, config, repository_config) {
+                    continue;
+                }
+                HashMap::new()
+            }
+        };
+
+        for release in releases.iter() {
+            let wanted = repository_config.tags.get(&release.version);
+            if wanted != current_tags.get(&release.version) {
+                plan.changes.push(Change::SetTag {
+                    project: project.name.clone(),
+                    github_name: repository_config.github_name.clone(),
+                    version: release.version.clone(),
+                    tag: wanted.cloned(),
+                });
+            }
+        }
+
+        for version in repository_config.tags.keys() {
+            if !releases.iter().any(|r| &r.version == version) {
+                plan.warnings.push(format!("{} has no release {}, so its tag in {} isn't set",
+                                           project.name, version, repository_config.github_name));
+            }
+        }
+    }
+
+    let current_bots = ::projects::bots(conn, project);
+    for email in config.bots.iter() {
+        if !current_bots.iter().any(|bot| same_name(bot, email)) {
+            plan.changes.push(Change::AddBot { project: project.name.clone(), email: email.clone() });
+        }
+    }
+    for email in current_bots.iter() {
+        if !config.bots.iter().any(|bot| same_name(bot, email)) {
+            plan.changes.push(Change::RemoveBot { project: project.name.clone(), email: email.clone() });
+        }
+    }
+
+    let mut moved = false;
+    for release in releases.iter() {
+        let release_config = config.releases.get(&release.version);
+
+        let previous = release_config.and_then(|r| r.previous.clone());
+        if previous != release.previous_version {
+            plan.changes.push(Change::SetPrevious {
+                project: project.name.clone(),
+                version: release.version.clone(),
+                previous: previous,
+            });
+            moved = true;
+        }
+
+        match release_config.and_then(|r| r.visible) {
+            Some(visible) if visible != release.visible => {
+                plan.changes.push(Change::SetVisible {
+                    project: project.name.clone(),
+                    version: release.version.clone(),
+                    visible: visible,
+                });
+            }
+            _ => {}
+        }
+    }
+
+    for version in config.releases.keys() {
+        if !releases.iter().any(|r| &r.version == version) {
+            plan.warnings.push(format!("{} has no release {}, so its settings aren't applied", project.name, version));
+        }
+    }
+
+    if moved {
+        plan.warnings.push(format!("Commits only move to the releases {} now follows after recompute-releases \
+                                    is run", project.name));
+    }
+}
+
+fn plan_repository(plan: &mut Plan, config: &RepositoryConfig, repository: &Repository) {
+    if repository.tag_pattern != config.tag_pattern || repository.mailmap != config.mailmap ||
+       repository.default_branch != config.default_branch {
+        plan.changes.push(Change::UpdateRepository {
+            github_name: repository.github_name.clone(),
+            tag_pattern: (repository.tag_pattern.clone(), config.tag_pattern.clone()),
+            mailmap: (repository.mailmap.clone(), config.mailmap.clone()),
+            default_branch: (repository.default_branch.clone(), config.default_branch.clone()),
+        });
+    }
+}
+
+/// GitHub names and emails are compared ignoring case
+fn same_name(a: &str, b: &str) -> bool {
+    a.to_lowercase() == b.to_lowercase()
+}
+
+/// makes the changes, all or nothing
+pub fn apply(conn: &PgConnection, changes: &[Change]) -> Result<(), Box<Error>> {
+    conn.transaction(|| {
+        for change in changes {
+            apply_change(conn, change)?;
+        }
+
+        Ok(())
+    })
+}
+
+fn apply_change(conn: &PgConnection, change: &Change) -> Result<(), Box<Error>> {
+    match *change {
+        Change::CreateProject { ref name, ref url_path, ref github_name } => {
+            ::projects::create(conn, name, url_path, github_name)?;
+        }
+        Change::UpdateProject { ref name, ref url_path, ref github_name } => {
+            ::projects::update(conn, &find_project(conn, name)?, &url_path.1, &github_name.1)?;
+        }
+        Change::CreateRepository { ref project, ref github_name, ref tag_pattern, ref mailmap, ref default_branch } => {
+            ::repositories::create(conn, &find_project(conn, project)?, github_name, tag_pattern,
+                                   mailmap.as_ref().map(String::as_str), default_branch)?;
+        }
+        Change::UpdateRepository { ref github_name, ref tag_pattern, ref mailmap, ref default_branch } => {
+            ::repositories::update(conn, &find_repository(conn, github_name)?, &tag_pattern.1,
+                                   mailmap.1.as_ref().map(String::as_str), &default_branch.1)?;
+        }
+        Change::AddBot { ref project, ref email } => {
+            ::projects::add_bot(conn, &find_project(conn, project)?, email)?;
+        }
+        Change::RemoveBot { ref project, ref email } => {
+            ::projects::remove_bot(conn, &find_project(conn, project)?, email)?;
+        }
+        Change::SetTag { ref project, ref github_name, ref version, ref tag } => {
+            let repository = find_repository(conn, github_name)?;
+            let release = find_release(conn, project, version)?;
+
+            match *tag {
+                Some(ref tag) => ::repositories::set_tag(conn, &repository, &release, tag)?,
+                None => ::repositories::remove_tag(conn, &repository, &release)?,
+            }
+        }
+        Change::SetPrevious { ref project, ref version, ref previous } => {
+            let release = find_release(conn, project, version)?;
+            ::releases::set_previous(conn, &release, previous.as_ref().map(String::as_str))?;
+        }
+        Change::SetVisible { ref project, ref version, visible } => {
+            let release = find_release(conn, project, version)?;
+            ::releases::set_visible(conn, &release, visible)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn find_project(conn: &PgConnection, name: &str) -> Result<Project, Box<Error>> {
+    ::projects::find_by_name(conn, name)
+        .ok_or_else(|| format!("Unknown project {}", name).into())
+}
+
+fn find_repository(conn: &PgConnection, github_name: &str) -> Result<Repository, Box<Error>> {
+    ::repositories::find_by_github_name(conn, github_name)
+        .ok_or_else(|| format!("Unknown repository {}", github_name).into())
+}
+
+fn find_release(conn: &PgConnection, project: &str, version: &str) -> Result<Release, Box<Error>> {
+    ::releases::find(conn, &find_project(conn, project)?, version)
+        .ok_or_else(|| format!("{} has no release {}", project, version).into())
+}
@@ -0,0 +1,252 @@
+//! Receiving GitHub's push webhooks.
+//!
+//! Every repository can have a secret; GitHub signs each delivery with it,
+//! and deliveries for repositories without one are refused. Commits pushed to
+//! a repository's default branch are added to its project's `master`
+//! release, just like `update-commit-db` would.
+
+use github::{self, PushedCommit};
+use mailmap::Mailmap;
+use models;
+use models::Project;
+
+use crypto::hmac::Hmac;
+use crypto::mac::{Mac, MacResult};
+use crypto::sha1::Sha1;
+
+use diesel::pg::PgConnection;
+
+use serde_json;
+
+use slog::Logger;
+
+use std::error::Error;
+
+/// GitHub only includes this many commits in a push
+const MAX_PUSH_COMMITS: usize = 20;
+
+#[derive(Debug, Deserialize)]
+struct PushEvent {
+    #[serde(rename = "ref")]
+    git_ref: String,
+    repository: Repository,
+    commits: Vec<Commit>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Repository {
+    full_name: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Commit {
+    id: String,
+    message: String,
+    timestamp: String,
+    author: Person,
+}
+
+#[derive(Debug, Deserialize)]
+struct Person {
+    name: String,
+    email: String,
+}
+
+/// why a delivery was refused
+#[derive(Debug, PartialEq)]
+pub enum WebhookError {
+    /// the body isn't an event we understand
+    BadPayload,
+    /// the signature is missing or wrong, or we don't know the repository, or
+    /// it has no secret
+    BadSignature,
+    /// the delivery was fine, but recording its commits failed
+    Failed(String),
+}
+
+/// what we did with a delivery
+#[derive(Debug, Serialize)]
+pub struct Delivery {
+    /// how many of the commits we didn't have yet
+    pub added: usize,
+}
+
+/// handles a delivery of the webhook, given the `X-GitHub-Event` and
+/// `X-Hub-Signature` headers and the raw body
+///
+/// events other than pushes, and pushes to other branches, are accepted but
+/// ignored
+///
+/// the signature is checked before anything in the body is looked at. since
+/// the body says which repository it's for, it's checked against the secret
+/// of every repository, and only the ones it matches can be the one named.
+pub fn receive(conn: &PgConnection, log: &Logger, event: &str, signature: Option<&str>, body: &[u8])
+    -> Result<Delivery, WebhookError>
+{
+    // the only other event we expect is the ping sent when the hook is added
+    if event != "push" {
+        info!(log, "Ignoring {} event", event);
+        return Ok(Delivery { added: 0 });
+    }
+
+    let signature = signature.ok_or(WebhookError::BadSignature)?;
+    let signed_by: Vec<models::Repository> = ::repositories::with_webhook_secret(conn)
+        .map_err(|e| WebhookError::Failed(e.to_string()))?
+        .into_iter()
+        .filter(|repository| {
+            repository.webhook_secret.as_ref().map_or(false, |secret| verify_signature(secret, body, signature))
+        })
+        .collect();
+    if signed_by.is_empty() {
+        warn!(log, "Refusing a push with a bad signature");
+        return Err(WebhookError::BadSignature);
+    }
+
+    let push: PushEvent = serde_json::from_slice(body).map_err(|e| {
+        warn!(log, "Refusing a push with an invalid payload: {}", e);
+        WebhookError::BadPayload
+    })?;
+
+    // unknown repositories are refused like bad signatures, so that nobody
+    // can find out which ones we know
+    let repository = signed_by.into_iter()
+        .find(|repository| repository.github_name.to_lowercase() == push.repository.full_name.to_lowercase())
+        .ok_or_else(|| {
+            warn!(log, "Refusing a push to {}, which isn't signed with its secret", push.repository.full_name);
+            WebhookError::BadSignature
+        })?;
+    let project = ::projects::find(conn, repository.project_id);
+
+    if push.git_ref != format!("refs/heads/{}", repository.default_branch) {
+        info!(log, "Ignoring a push to {} of {}", push.git_ref, repository.github_name);
+        return Ok(Delivery { added: 0 });
+    }
+
+    let added = record_push(conn, log, &project, &repository, &push)
+        .map_err(|e| WebhookError::Failed(e.to_string()))?;
+
+    Ok(Delivery { added: added })
+}
+
+fn record_push(conn: &PgConnection, log: &Logger, project: &Project, repository: &models::Repository,
+               push: &PushEvent) -> Result<usize, Box<Error>> {
+    // GitHub leaves out commits past the first few, so the rest of a big
+    // push is left to update-commit-db; fetching them here could take longer
+    // than GitHub waits for us to answer
+    if push.commits.len() >= MAX_PUSH_COMMITS {
+        info!(log, "Asking for a sync of {}, since the push was too big to include every commit",
+              repository.github_name);
+        ::repositories::request_sync(conn, repository)?;
+    }
+
+    // fetching the mailmap could take longer than GitHub waits too, so until
+    // it's cached the commits are left to update-commit-db as well
+    let mailmap = match Mailmap::for_github_cached(repository) {
+        Some(mailmap) => mailmap,
+        None => {
+            info!(log, "Asking for a sync of {}, since its mailmap hasn't been fetched yet", repository.github_name);
+            ::repositories::request_sync(conn, repository)?;
+            return Ok(0);
+        }
+    };
+    let master = ::releases::master(conn, project)?;
+
+    let mut added = 0;
+    for commit in push.commits.iter() {
+        // the payload only has the one date
+        let date = github::parse_date(&commit.timestamp)?;
+
+        let pushed = PushedCommit {
+            sha: &commit.id,
+            author_name: &commit.author.name,
+            author_email: &commit.author.email,
+            authored_at: date,
+            committed_at: date,
+            message: &commit.message,
+        };
+        if github::record(conn, log, &master, &mailmap, &pushed)? {
+            added += 1;
+        }
+    }
+
+    Ok(added)
+}
+
+/// checks an `X-Hub-Signature` header, which looks like `sha1=<hex digest>`,
+/// against the HMAC of the body
+pub fn verify_signature(secret: &str, body: &[u8], signature: &str) -> bool {
+    let mut parts = signature.trim().splitn(2, '=');
+
+    let expected = match (parts.next(), parts.next().and_then(from_hex)) {
+        (Some("sha1"), Some(expected)) => expected,
+        _ => return false,
+    };
+
+    let mut hmac = Hmac::new(Sha1::new(), secret.as_bytes());
+    hmac.input(body);
+
+    // MacResult compares in constant time
+    hmac.result() == MacResult::new(&expected)
+}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 || !hex.chars().all(|c| c.is_digit(16)) {
+        return None;
+    }
+
+    (0..hex.len() / 2)
+        .map(|i| u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{from_hex, verify_signature};
+
+    const SECRET: &'static str = "It's a Secret to Everybody";
+    const BODY: &'static [u8] = b"Hello, World!";
+
+    #[test]
+    fn accepts_the_right_signature() {
+        assert!(verify_signature(SECRET, BODY, "sha1=01dc10d0c83e72ed246219cdd91669667fe2ca59"));
+        assert!(verify_signature(SECRET, BODY, "sha1=01DC10D0C83E72ED246219CDD91669667FE2CA59"));
+    }
+
+    #[test]
+    fn refuses_a_wrong_signature() {
+        assert!(!verify_signature(SECRET, BODY, "sha1=01dc10d0c83e72ed246219cdd91669667fe2ca58"));
+        assert!(!verify_signature("another secret", BODY, "sha1=01dc10d0c83e72ed246219cdd91669667fe2ca59"));
+        assert!(!verify_signature(SECRET, b"Hello, World?", "sha1=01dc10d0c83e72ed246219cdd91669667fe2ca59"));
+    }
+
+    #[test]
+    fn refuses_a_signature_without_its_prefix() {
+        assert!(!verify_signature(SECRET, BODY, "01dc10d0c83e72ed246219cdd91669667fe2ca59"));
+        assert!(!verify_signature(SECRET, BODY, "sha256=01dc10d0c83e72ed246219cdd91669667fe2ca59"));
+    }
+
+    #[test]
+    fn refuses_a_signature_that_isnt_hex() {
+        assert!(!verify_signature(SECRET, BODY, "sha1=01dc10d0c83e72ed246219cdd91669667fe2ca5"));
+        assert!(!verify_signature(SECRET, BODY, "sha1=01dc10d0c83e72ed246219cdd91669667fe2ca5g"));
+        assert!(!verify_signature(SECRET, BODY, "sha1="));
+    }
+
+    #[test]
+    fn parses_hex() {
+        assert_eq!(from_hex("00ff7F"), Some(vec![0x00, 0xff, 0x7f]));
+        assert_eq!(from_hex(""), Some(vec![]));
+    }
+
+    #[test]
+    fn rejects_odd_length_hex() {
+        assert_eq!(from_hex("abc"), None);
+    }
+
+    #[test]
+    fn rejects_non_hex() {
+        assert_eq!(from_hex("zz"), None);
+        assert_eq!(from_hex("+1"), None);
+        assert_eq!(from_hex("é"), None);
+    }
+}